@@ -0,0 +1,54 @@
+//! Builds a very wide JSON config and reports how many allocations `build()` makes.
+//! Run with `cargo run --release --example wide`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use ronf::{Config, File, FileFormat};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const KEYS: usize = 200_000;
+
+fn main() {
+    let mut json = String::from("{");
+    for i in 0..KEYS {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str(&format!("\"key{}\": {}", i, i));
+    }
+    json.push('}');
+    let file = File::new_str("wide.json", FileFormat::Json, &json);
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let config = Config::builder().add_file(file).build().unwrap();
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    println!(
+        "{} keys built in {:?} with {} allocations ({:.2} per key)",
+        config.list().len(),
+        elapsed,
+        allocations,
+        allocations as f64 / KEYS as f64
+    );
+}
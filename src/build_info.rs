@@ -0,0 +1,75 @@
+//! Reflection on how the crate was compiled
+
+use alloc::vec::Vec;
+
+/// Version of the crate and the Cargo features it was compiled with, handy for bug reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Crate version, like `0.5.0`.
+    pub version: &'static str,
+    /// Enabled features, like `["std", "json"]`.
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    /// Whether a feature was compiled in, like `has_feature("yaml")`.
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+impl core::fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ronf {} (features: ", self.version)?;
+        match self.features.is_empty() {
+            true => write!(f, "none")?,
+            false => write!(f, "{}", self.features.join(", "))?,
+        }
+        write!(f, ")")
+    }
+}
+
+/// Returns the crate version and the features it was compiled with.
+pub fn build_info() -> BuildInfo {
+    let features = [
+        ("std", cfg!(feature = "std")),
+        ("json", cfg!(feature = "json")),
+        ("yaml", cfg!(feature = "yaml")),
+        ("ini", cfg!(feature = "ini")),
+        ("toml", cfg!(feature = "toml")),
+        ("ron", cfg!(feature = "ron")),
+        ("env", cfg!(feature = "env")),
+        ("read_file", cfg!(feature = "read_file")),
+        ("load_after_build", cfg!(feature = "load_after_build")),
+        ("i128", cfg!(feature = "i128")),
+        ("intern", cfg!(feature = "intern")),
+        ("jsonschema", cfg!(feature = "jsonschema")),
+    ];
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        features: features
+            .into_iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert!(!info.version.is_empty());
+        assert_eq!(info.has_feature("std"), cfg!(feature = "std"));
+        assert_eq!(info.has_feature("json"), cfg!(feature = "json"));
+        assert_eq!(info.has_feature("yaml"), cfg!(feature = "yaml"));
+        assert!(!info.has_feature("unknown"));
+        assert!(
+            info.to_string()
+                .starts_with(&format!("ronf {} (", info.version))
+        );
+    }
+}
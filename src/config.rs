@@ -1,62 +1,224 @@
 //! Configuration structure
 
-use crate::file::{File, FileFormat};
-use crate::value::{Map, Value};
+use crate::error::{CannotConvert, ConfigError};
+#[cfg(any(feature = "ini", feature = "json", feature = "toml"))]
+use crate::file::format_error;
+use crate::file::{File, FileFormat, ParseOptions, RepeatedKeyPolicy, parse_content};
+use crate::path::split_path;
+use crate::value::{Map, Table, Value, ValueKind};
+use crate::warning::Warning;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// How arrays under the same key are combined when layering files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrayMergePolicy {
+    /// The later array replaces the earlier one
+    #[default]
+    Replace,
+    /// The later array is appended to the earlier one
+    Concat,
+    /// Like `Concat`, but values already present are skipped
+    Unique,
+}
+
+/// What happens when several files set the same key to different values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// The later file silently wins
+    #[default]
+    Ignore,
+    /// The later file wins and a `Warning::Conflict` is recorded
+    Warn,
+    /// `build()` fails listing every conflict
+    Error,
+}
+
+/// How nested tables and arrays are written when saving as YAML
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum YamlStyle {
+    /// One entry per line, nesting shown by indentation
+    #[default]
+    Block,
+    /// Everything on one line, like `{key: [1, 2]}`
+    Flow,
+}
+
+/// Options that change how a config is serialized on save
+#[derive(Debug, Clone)]
+pub(crate) struct SaveOptions {
+    pub toml_inline_tables: bool,
+    pub yaml_style: YamlStyle,
+    pub yaml_document_marker: bool,
+    pub format: Option<FileFormat>,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions {
+            toml_inline_tables: false,
+            yaml_style: YamlStyle::default(),
+            yaml_document_marker: true,
+            format: None,
+        }
+    }
+}
 
 /// Builder for the Config struct
 pub struct ConfigBuilder {
     pub files: Vec<File>,
     pub changes: Map<String, Value>,
+    pub(crate) deprecations: Vec<(String, Option<String>)>,
+    pub(crate) base_dir: Option<PathBuf>,
+    pub(crate) expectations: Vec<(String, ValueKind)>,
+    pub(crate) array_merge: ArrayMergePolicy,
+    pub(crate) conflicts: ConflictPolicy,
+    pub(crate) env_ignore: Vec<String>,
+    pub(crate) env_prefix: Option<String>,
+    pub(crate) env_separator: String,
+    pub(crate) overrides: Vec<(String, Value)>,
+    pub(crate) secrets: Vec<String>,
+    pub(crate) require_non_empty: bool,
+    pub(crate) decimal_comma: bool,
+    pub(crate) allow_new_keys: bool,
+    pub(crate) key_capacity: usize,
+    pub(crate) changes_file: Option<File>,
+    /// Keys files added by `add_dir_nested()` are nested under, by their index in `files`
+    pub(crate) nest_keys: Map<usize, String>,
+    pub(crate) parse_options: ParseOptions,
+    pub(crate) save_options: SaveOptions,
 }
 
 impl ConfigBuilder {
-    /// Creates a new ConfigBuilder instance
-    pub fn build(self) -> Result<Config, String> {
+    /// Builds the config by applying layers in a fixed order, each one overwriting the ones
+    /// before it:
+    ///
+    /// 1. files, in the order they were added, which form the defaults
+    /// 2. env vars, with the `env` feature
+    /// 3. values pinned with `override_value()`
+    /// 4. changes loaded with `load()`
+    ///
+    /// Deprecations and `expect()` are checked against the result of all layers
+    pub fn build(self) -> Result<Config, ConfigError> {
         let mut config = Config {
-            defaults: Map::new(),
+            defaults: None,
             changes: Map::new(),
-            values: Map::new(),
+            values: Map::with_capacity(self.key_capacity),
+            warnings: Vec::new(),
+            sources: Map::with_capacity(self.key_capacity),
+            layer_sources: Map::new(),
+            descriptions: Map::new(),
+            secrets: self.secrets,
+            decimal_comma: self.decimal_comma,
+            allow_new_keys: self.allow_new_keys,
+            array_merge: self.array_merge,
+            parse_options: self.parse_options,
+            overrides: self.overrides,
+            #[cfg(feature = "env")]
+            env_prefix: self.env_prefix,
+            #[cfg(feature = "env")]
+            env_separator: self.env_separator,
+            #[cfg(feature = "env")]
+            env_ignore: self.env_ignore,
+            #[cfg(feature = "env")]
+            env_overrides: Vec::new(),
+            #[cfg(feature = "intern")]
+            interned: false,
+            base_dir: self.base_dir,
+            save_options: self.save_options,
         };
 
-        for file in self.files {
+        let mut conflicts = Vec::new();
+        for (i, file) in self.files.into_iter().enumerate() {
+            let nest_key = self.nest_keys.get(&i).map(String::as_str);
             let parsed = file
-                .parse()
-                .map_err(|e| format!("Failed to parse file {}: {}", file.path, e))?;
-            config.defaults.extend(parsed);
-        }
-
-        config.values = config.defaults.clone();
-
-        for (key, value) in self.changes.iter() {
-            if config.values.contains_key(key) {
-                config.values.insert(key.clone(), value.clone());
+                .parse_nested(&config.parse_options, nest_key)
+                .map_err(|e| ConfigError::parse(&file.path, e))?;
+            let descriptions = file.descriptions().into_iter();
+            match nest_key {
+                Some(key) => config.descriptions.extend(
+                    descriptions
+                        .map(|(path, description)| (format!("{}.{}", key, path), description)),
+                ),
+                None => config.descriptions.extend(descriptions),
+            }
+            let path: Arc<str> = Arc::from(file.path.as_str());
+            for (key, value) in parsed.iter() {
+                let merged = config.array_merge != ArrayMergePolicy::Replace && value.is_array();
+                if let (Some(existing), Some(origin)) =
+                    (config.values.get(key), config.sources.get(key))
+                {
+                    if self.conflicts != ConflictPolicy::Ignore
+                        && existing != value
+                        && !(merged && existing.is_array())
+                    {
+                        conflicts.push(Warning::Conflict {
+                            key: key.clone(),
+                            first: origin.to_string(),
+                            second: file.path.clone(),
+                        });
+                    }
+                }
+                config.sources.insert(key.clone(), path.clone());
             }
+            merge_map(&mut config.values, parsed, config.array_merge);
         }
+        if self.conflicts == ConflictPolicy::Error && !conflicts.is_empty() {
+            let conflicts = conflicts.iter().map(|c| c.to_string()).collect();
+            return Err(ConfigError::Conflicts(conflicts));
+        }
+        config.warnings.extend(conflicts);
 
         #[cfg(feature = "env")]
-        {
-            let env_vars = get_env_vars();
-            for (key, value) in env_vars.iter() {
-                let key = key.to_lowercase();
-                let mut key_parts: Vec<&str> = key.split('_').collect();
-                key_parts.retain(|&part| !part.is_empty());
-                if key_parts.is_empty() {
-                    continue;
-                }
+        config.apply_env();
+        config.apply_overrides();
+        let changes = match self.changes_file {
+            Some(file) => file
+                .parse_with(&config.parse_options)
+                .map_err(|e| ConfigError::parse(&file.path, e))?,
+            None => self.changes,
+        };
+        config.changes = changes.clone();
+        config.apply_changes(changes);
 
-                let val = match config.values.get(key_parts[0]) {
-                    Some(v) => v,
-                    None => {
-                        continue;
+        for (old, new) in self.deprecations.iter() {
+            let value = match config.values.get(old) {
+                Some(v) => v.clone(),
+                None => continue,
+            };
+            config.warnings.push(Warning::Deprecated {
+                key: old.clone(),
+                replacement: new.clone(),
+            });
+            if let Some(new) = new {
+                if !config.values.contains_key(new) {
+                    config.keep_defaults();
+                    config.values.insert(new.clone(), value);
+                    if let Some(origin) = config.source(old).cloned() {
+                        config.layer_sources.insert(new.clone(), origin);
                     }
-                };
-                if !val.is_table() {
-                    *config.values.get_mut(key_parts[0]).unwrap() = value.clone();
-                    continue;
                 }
             }
         }
 
+        let mismatches: Vec<String> = self
+            .expectations
+            .iter()
+            .filter_map(|(path, kind)| {
+                let found = get_path(&config.values, path)?.kind();
+                (found != *kind).then(|| format!("{} expected {}, found {}", path, kind, found))
+            })
+            .collect();
+        if !mismatches.is_empty() {
+            return Err(ConfigError::TypeMismatch(mismatches));
+        }
+
+        if self.require_non_empty && config.values.is_empty() {
+            return Err(ConfigError::EmptyConfig);
+        }
+
         Ok(config)
     }
 
@@ -66,7 +228,235 @@ impl ConfigBuilder {
         self
     }
 
+    /// Shorter name for `add_file()`
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, file: File) -> Self {
+        self.add_file(file)
+    }
+
+    /// Paths of the added files in the order they will be applied, later ones win
+    pub fn file_paths(&self) -> Vec<&str> {
+        self.files.iter().map(|f| f.path.as_str()).collect()
+    }
+
+    /// Adds the first of `paths` that exists, like `./app.json` then `/etc/app.json`.
+    /// Adds nothing if none of them exist, but fails if the found file can't be read.
+    #[cfg(feature = "read_file")]
+    pub fn add_first_existing(self, paths: &[&str]) -> Result<Self, ConfigError> {
+        match paths.iter().find(|path| Path::new(path).is_file()) {
+            Some(path) => Ok(self.add_file(File::from_path(path.to_string())?)),
+            None => Ok(self),
+        }
+    }
+
+    /// Adds every file with a supported extension in `dir`, conf.d style, each nested under
+    /// its file name without the extension, so `redis.toml` is read under `redis`. Files are
+    /// applied in name order, other entries are skipped
+    #[cfg(feature = "read_file")]
+    pub fn add_dir_nested<P: AsRef<Path>>(mut self, dir: P) -> Result<Self, ConfigError> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir).map_err(|e| dir_error(dir, e))?;
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| dir_error(dir, e))?.path();
+            let supported = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(FileFormat::from_extension)
+                .is_some();
+            if path.is_file() && supported {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        for path in paths {
+            let stem = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| ConfigError::Io {
+                    kind: std::io::ErrorKind::InvalidInput,
+                    message: format!("Invalid file name {}", path.display()),
+                })?
+                .to_string();
+            let file = File::from_path(path.to_string_lossy().into_owned())?;
+            self.nest_keys.insert(self.files.len(), stem);
+            self.files.push(file);
+        }
+        Ok(self)
+    }
+
+    /// Sets the directory relative paths in the config are resolved against, usually the
+    /// directory of the config file. Without it relative paths are left relative to the CWD.
+    pub fn base_dir<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.base_dir = Some(path.into());
+        self
+    }
+
+    /// Declares that the value at a dot-separated path must be of a given kind. `build()`
+    /// fails listing every mismatching path, missing paths are not checked.
+    pub fn expect(mut self, path: &str, kind: ValueKind) -> Self {
+        self.expectations.push((path.to_string(), kind));
+        self
+    }
+
+    /// Sets how arrays defined under the same key by several files are combined.
+    /// Defaults to `ArrayMergePolicy::Replace`.
+    pub fn array_merge(mut self, policy: ArrayMergePolicy) -> Self {
+        self.array_merge = policy;
+        self
+    }
+
+    /// Makes `build()` fail if the config ends up without any key, like when every file was
+    /// missing or pointed at the wrong directory
+    pub fn require_non_empty(mut self) -> Self {
+        self.require_non_empty = true;
+        self
+    }
+
+    /// Records a `Warning::Conflict` when several files set a key to different values.
+    /// Use `conflicts(ConflictPolicy::Error)` to fail the build instead.
+    pub fn detect_conflicts(mut self, detect: bool) -> Self {
+        self.conflicts = if detect {
+            ConflictPolicy::Warn
+        } else {
+            ConflictPolicy::Ignore
+        };
+        self
+    }
+
+    /// Sets what happens when several files set a key to different values.
+    /// Defaults to `ConflictPolicy::Ignore`.
+    pub fn conflicts(mut self, policy: ConflictPolicy) -> Self {
+        self.conflicts = policy;
+        self
+    }
+
+    /// Sets how keys repeated within an INI section are read.
+    /// Defaults to `RepeatedKeyPolicy::Last`.
+    pub fn ini_repeated_keys(mut self, policy: RepeatedKeyPolicy) -> Self {
+        self.parse_options.ini_repeated_keys = policy;
+        self
+    }
+
+    /// Allows `//` and `/* */` comments in JSON files. Defaults to strict JSON.
+    pub fn json_allow_comments(mut self, allow: bool) -> Self {
+        self.parse_options.json_allow_comments = allow;
+        self
+    }
+
+    /// Accepts a comma as the decimal separator when a float getter like `Config::get_float()` or
+    /// `Config::get_f64_clamped()` converts a string such as `"3,14"` to a number. The comma is
+    /// never read as a thousands separator, so `"1,500"` is `1.5`, and strings that also hold a
+    /// dot like `"1,000.5"` are rejected. Native numbers in files and other getters like
+    /// `get_string()` are not affected
+    pub fn decimal_comma(mut self, enabled: bool) -> Self {
+        self.decimal_comma = enabled;
+        self
+    }
+
+    /// Keeps keys from `load()` that no file defines instead of ignoring them with a
+    /// `Warning::IgnoredChange`, both in `build()` and in `Config::load()`. Keys are checked
+    /// at every level, so a new `server.debug` is ignored even if `server` exists
+    pub fn allow_new_keys(mut self, allow: bool) -> Self {
+        self.allow_new_keys = allow;
+        self
+    }
+
+    /// Sets the format used by `Config::save_default()`
+    pub fn save_format(mut self, format: FileFormat) -> Self {
+        self.save_options.format = Some(format);
+        self
+    }
+
+    /// Sets whether YAML is saved in block or flow style. Defaults to `YamlStyle::Block`.
+    pub fn yaml_style(mut self, style: YamlStyle) -> Self {
+        self.save_options.yaml_style = style;
+        self
+    }
+
+    /// Sets whether saved YAML starts with the `---` document marker. Defaults to `true`.
+    pub fn yaml_document_marker(mut self, marker: bool) -> Self {
+        self.save_options.yaml_document_marker = marker;
+        self
+    }
+
+    /// Pins a value under a dotted path (`"server.port"`), applied after files and env vars so
+    /// only changes from `load()` take precedence over it. Missing parent tables are created.
+    /// The value is not saved.
+    /// Keys containing dots can be quoted, like `"\"my.key\".sub"`.
+    pub fn override_value<V: Into<Value>>(mut self, path: &str, value: V) -> Self {
+        self.overrides.push((path.to_string(), value.into()));
+        self
+    }
+
+    /// Keys or dotted paths like `"db.password"` that env vars can never overwrite, along with
+    /// everything nested under them, even with the `env` feature enabled.
+    /// Env vars otherwise take precedence over files, see `build()` for the full order.
+    pub fn env_ignore(mut self, keys: &[&str]) -> Self {
+        self.env_ignore
+            .extend(keys.iter().map(|key| key.to_lowercase()));
+        self
+    }
+
+    /// Only env vars starting with `prefix` (ignoring case) are used, with the prefix stripped,
+    /// so `MYAPP_PORT` overwrites `port` with `env_prefix("MYAPP_")`. The trailing `_` is implied,
+    /// so `env_prefix("MYAPP")` matches the same vars. With a prefix, vars for keys
+    /// no file defines add them as strings, creating missing tables, so `MYAPP_DB__HOST` adds
+    /// `db.host`. Without a prefix every env var is considered, so unrelated ones like `PATH` or
+    /// `HOME` overwrite keys of that name, but only keys that already exist are overwritten.
+    pub fn env_prefix(mut self, prefix: &str) -> Self {
+        self.env_prefix = Some(prefix.to_lowercase());
+        self
+    }
+
+    /// Separator between nested keys in env var names, `__` by default. With `__`,
+    /// `APP__FULL_SCREEN` overwrites `app.full_screen`, while with `_` it would look for
+    /// `app.full.screen`. An empty separator keeps the default `__`.
+    pub fn env_separator(mut self, separator: &str) -> Self {
+        self.env_separator = separator.to_lowercase();
+        self
+    }
+
+    /// Writes tables with at most 4 keys inline when saving to TOML, as `{ a = 1 }` instead of
+    /// a `[table]` section. Nested tables are handled first, so a small table whose own tables
+    /// were all inlined is inlined as well, while one holding a larger table stays a section.
+    pub fn toml_inline_tables(mut self, inline: bool) -> Self {
+        self.save_options.toml_inline_tables = inline;
+        self
+    }
+
+    /// Keys or dotted paths whose values always stay strings, for identifiers that look like
+    /// numbers such as ZIP codes or versions like `1.10`. Numbers read from files at these
+    /// paths are turned back into strings, keeping the exact text for YAML and TOML, and
+    /// `Config::infer_types()` leaves them alone
+    pub fn preserve_string_numbers(mut self, paths: &[&str]) -> Self {
+        self.parse_options
+            .preserve_strings
+            .extend(paths.iter().map(|p| p.to_string()));
+        self
+    }
+
+    /// Marks a key or dotted path as secret, `Display` for `Config` prints `***` instead of its value.
+    /// Keys containing dots can be quoted, and a path into an array of tables applies to every
+    /// table in it
+    pub fn secret(mut self, path: &str) -> Self {
+        self.secrets.push(path.to_string());
+        self
+    }
+
+    /// Marks a key as deprecated. If the key is present after building, a warning is
+    /// recorded in `Config::warnings()`, and its value is copied to `new` if `new` is unset.
+    pub fn deprecate(mut self, old: &str, new: Option<&str>) -> Self {
+        self.deprecations
+            .push((old.to_string(), new.map(|n| n.to_string())));
+        self
+    }
+
     /// Loads changes to default configuration from `.add_file()` from a file.
+    /// Nested tables are merged with the ones from files, arrays replace them whole.
+    /// The changes are kept in the built config, so `Config::save()` writes them back.
+    /// The file is parsed by `build()` with the builder's parse options, including the ones set
+    /// after `load()`, and parse errors are returned from there. Loading again replaces the file.
     /// Example:
     /// ```rust
     /// #[cfg(features = "json")]
@@ -92,19 +482,255 @@ impl ConfigBuilder {
     /// println!("\"key\" after load: {}", loaded_config.get("key").unwrap());
     /// }
     /// ```
-    pub fn load(mut self, file: File) -> Result<Self, String> {
-        self.changes = load_map(file.content, file.format)?;
+    pub fn load(mut self, file: File) -> Result<Self, ConfigError> {
+        self.changes_file = Some(file);
         Ok(self)
     }
 }
 
+/// Merges `source` into `target`, combining arrays under the same key by `policy`
+fn merge_map(
+    target: &mut Map<String, Value>,
+    source: Map<String, Value>,
+    policy: ArrayMergePolicy,
+) {
+    if target.is_empty() && target.capacity() <= source.len() {
+        *target = source;
+        return;
+    }
+    for (key, value) in source {
+        match (target.get_mut(&key), value) {
+            (Some(Value::Array(existing)), Value::Array(array)) => match policy {
+                ArrayMergePolicy::Replace => *existing = array,
+                ArrayMergePolicy::Concat => existing.extend(array),
+                ArrayMergePolicy::Unique => {
+                    for item in array {
+                        if !existing.contains(&item) {
+                            existing.push(item);
+                        }
+                    }
+                }
+            },
+            (_, value) => {
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Merges `source` into `target`, recursing into tables both have and replacing everything else
+fn merge_deep(target: &mut Map<String, Value>, source: Map<String, Value>) {
+    for (key, value) in source {
+        match (target.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(table)) => merge_deep(existing, table),
+            (_, value) => {
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Deep-merges `source` into `target` like `merge_deep`. Unless `allow_new`, keys missing from
+/// `target` are skipped at every level and their dotted paths under `prefix` are collected in
+/// `ignored`. Returns whether anything was merged
+fn merge_changes(
+    target: &mut Map<String, Value>,
+    source: Map<String, Value>,
+    allow_new: bool,
+    prefix: &str,
+    ignored: &mut Vec<String>,
+) -> bool {
+    let mut merged = false;
+    for (key, value) in source {
+        let path = || match prefix {
+            "" => key.clone(),
+            prefix => format!("{}.{}", prefix, key),
+        };
+        match (target.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(table)) => {
+                merged |= merge_changes(existing, table, allow_new, &path(), ignored);
+            }
+            (None, _) if !allow_new => ignored.push(path()),
+            (_, value) => {
+                target.insert(key, value);
+                merged = true;
+            }
+        }
+    }
+    merged
+}
+
+/// Replaces every string under `value` with a `Value::Shared` taken from `pool`
+#[cfg(feature = "intern")]
+fn intern_in(value: &mut Value, pool: &mut std::collections::HashSet<Arc<str>>) {
+    let shared = match value {
+        Value::String(s) => pool.get(s.as_str()).cloned().unwrap_or_else(|| {
+            let shared: Arc<str> = Arc::from(s.as_str());
+            pool.insert(shared.clone());
+            shared
+        }),
+        Value::Shared(s) => match pool.get(s) {
+            Some(shared) => shared.clone(),
+            None => {
+                pool.insert(s.clone());
+                return;
+            }
+        },
+        Value::Array(array) => return array.iter_mut().for_each(|v| intern_in(v, pool)),
+        Value::Table(table) => return table.values_mut().for_each(|v| intern_in(v, pool)),
+        _ => return,
+    };
+    *value = Value::Shared(shared);
+}
+
+/// Rewrites a string like `"3,14"` to `"3.14"` so it parses as a float, leaving anything else alone.
+/// A single comma is always the decimal separator, so `"1,500"` is `1.5`. Strings that mix in
+/// dots or hold more than one comma, like `"1,000.5"`, are left alone and fail to parse
+fn decimal_comma_to_dot(value: Value) -> Value {
+    let replaced = value.as_str().and_then(|s| {
+        let (whole, fraction) = s.split_once(',')?;
+        (!fraction.contains(',') && !s.contains('.')).then(|| format!("{}.{}", whole, fraction))
+    });
+    replaced.map(Value::String).unwrap_or(value)
+}
+
+/// Replaces every leaf under `value` with `f(leaf)`
+fn map_leaves(value: &mut Value, f: &mut impl FnMut(&Value) -> Value) {
+    match value {
+        Value::Table(table) => table.values_mut().for_each(|v| map_leaves(v, f)),
+        Value::Array(array) => array.iter_mut().for_each(|v| map_leaves(v, f)),
+        leaf => *leaf = f(leaf),
+    }
+}
+
+/// Sets the value at `path`, creating or replacing parent values with tables as needed
+fn set_in(map: &mut Map<String, Value>, path: &[&str], value: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut table = map;
+    for key in parents {
+        let entry = table
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Table(Map::new()));
+        if !entry.is_table() {
+            *entry = Value::Table(Map::new());
+        }
+        table = entry.as_table_mut().unwrap();
+    }
+    table.insert(last.to_string(), value);
+}
+
+/// Inserts `value` at `path`, creating missing parent tables. Returns `false` without changing
+/// anything if a value other than a table is in the way
 #[cfg(feature = "env")]
-fn get_env_vars() -> Map<String, Value> {
-    let mut env_vars = Map::new();
-    for (key, value) in std::env::vars() {
-        env_vars.insert(key, Value::String(value));
+fn insert_in(map: &mut Map<String, Value>, path: &[&str], value: Value) -> bool {
+    let mut table = &*map;
+    for key in &path[..path.len().saturating_sub(1)] {
+        match table.get(*key) {
+            Some(Value::Table(inner)) => table = inner,
+            Some(_) => return false,
+            None => break,
+        }
+    }
+    set_in(map, path, value);
+    true
+}
+
+/// Looks up a value by a path of keys through nested tables
+#[cfg(feature = "env")]
+fn get_in<'a>(map: &'a Map<String, Value>, path: &[&str]) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+    rest.iter()
+        .try_fold(map.get(*first)?, |value, key| value.get(key))
+}
+
+/// Collects the leaves of `table` under dotted paths starting with `prefix`
+fn flatten_into<'a>(
+    prefix: &str,
+    table: &'a Map<String, Value>,
+    out: &mut Vec<(String, &'a Value)>,
+) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Table(inner) if !inner.is_empty() => flatten_into(&path, inner, out),
+            _ => out.push((path, value)),
+        }
+    }
+}
+
+/// Inserts clones of the keys of `template` missing from `target`, recursing into tables both have
+fn fill_in(target: &mut Map<String, Value>, template: &Map<String, Value>) -> Vec<String> {
+    let mut added = Vec::new();
+    for (key, value) in template {
+        match (target.get_mut(key), value) {
+            (None, _) => {
+                target.insert(key.clone(), value.clone());
+                added.push(key.clone());
+            }
+            (Some(Value::Table(inner)), Value::Table(template)) => {
+                fill_in(inner, template);
+            }
+            _ => {}
+        }
+    }
+    added
+}
+
+/// Removes the value at `path` through nested tables, dropping tables it leaves empty if `prune`
+fn remove_in(map: &mut Map<String, Value>, path: &[&str], prune: bool) -> Option<Value> {
+    let (first, rest) = path.split_first()?;
+    if rest.is_empty() {
+        return map.shift_remove(*first);
     }
-    env_vars
+    let table = map.get_mut(*first)?.as_table_mut()?;
+    let removed = remove_in(table, rest, prune)?;
+    if prune && table.is_empty() {
+        map.shift_remove(*first);
+    }
+    Some(removed)
+}
+
+/// Looks up a value by a dot-separated path through nested tables, see `split_path`
+fn get_path<'a>(map: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+    let keys = split_path(path);
+    let (first, rest) = keys.split_first()?;
+    rest.iter()
+        .try_fold(map.get(first)?, |value, key| value.get(key))
+}
+
+#[cfg(feature = "read_file")]
+fn dir_error(dir: &Path, error: std::io::Error) -> ConfigError {
+    ConfigError::io(format!("Failed to read directory {}", dir.display()), error)
+}
+
+#[cfg(feature = "env")]
+fn get_env_vars() -> Map<String, String> {
+    std::env::vars().collect()
+}
+
+/// Parses an env var into the kind of the value it replaces. Bools accept the words of
+/// `Value::coerce_bool` like `yes` and `off`, and integers above `i64::MAX` are read as `u64`.
+/// It stays a string when the existing value is a string or another kind, or when it doesn't parse
+#[cfg(feature = "env")]
+fn coerce_env(value: String, existing: &Value) -> Value {
+    let trimmed = value.trim();
+    let parsed = match existing.kind() {
+        ValueKind::Int => trimmed
+            .parse()
+            .map(Value::Int)
+            .or_else(|_| trimmed.parse::<u64>().map(Value::from))
+            .ok(),
+        ValueKind::Float => trimmed.parse().ok().map(Value::Float),
+        ValueKind::Bool => crate::value::parse_bool(trimmed).map(Value::Bool),
+        _ => None,
+    };
+    parsed.unwrap_or(Value::String(value))
 }
 
 /// Configuration structure to hold parsed values
@@ -120,171 +746,1125 @@ fn get_env_vars() -> Map<String, Value> {
 /// }
 /// ```
 pub struct Config {
-    defaults: Map<String, Value>,
+    /// Values of the files alone, `None` until the first write of another layer or edit,
+    /// as `values` hold nothing else until then
+    defaults: Option<Map<String, Value>>,
     changes: Map<String, Value>,
     values: Map<String, Value>,
+    warnings: Vec<Warning>,
+    sources: Map<String, Arc<str>>,
+    layer_sources: Map<String, Arc<str>>,
+    descriptions: Map<String, String>,
+    secrets: Vec<String>,
+    decimal_comma: bool,
+    allow_new_keys: bool,
+    array_merge: ArrayMergePolicy,
+    parse_options: ParseOptions,
+    overrides: Vec<(String, Value)>,
+    #[cfg(feature = "env")]
+    env_prefix: Option<String>,
+    #[cfg(feature = "env")]
+    env_separator: String,
+    #[cfg(feature = "env")]
+    env_ignore: Vec<String>,
+    #[cfg(feature = "env")]
+    env_overrides: Vec<String>,
+    #[cfg(feature = "intern")]
+    interned: bool,
+    base_dir: Option<PathBuf>,
+    save_options: SaveOptions,
 }
 
 impl Config {
     /// Creates a ConfigBuilder
     pub fn builder() -> ConfigBuilder {
+        Config::builder_with_capacity(0, 0)
+    }
+
+    /// Creates a ConfigBuilder with room for `files` files and `keys` top-level keys.
+    /// Building a large config then fills pre-sized maps instead of growing and
+    /// rehashing them key by key
+    pub fn builder_with_capacity(files: usize, keys: usize) -> ConfigBuilder {
         ConfigBuilder {
-            files: Vec::new(),
+            files: Vec::with_capacity(files),
             changes: Map::new(),
+            deprecations: Vec::new(),
+            base_dir: None,
+            expectations: Vec::new(),
+            array_merge: ArrayMergePolicy::default(),
+            conflicts: ConflictPolicy::default(),
+            env_ignore: Vec::new(),
+            env_prefix: None,
+            env_separator: "__".to_string(),
+            overrides: Vec::new(),
+            secrets: Vec::new(),
+            require_non_empty: false,
+            decimal_comma: false,
+            allow_new_keys: false,
+            key_capacity: keys,
+            changes_file: None,
+            nest_keys: Map::new(),
+            parse_options: ParseOptions::default(),
+            save_options: SaveOptions::default(),
         }
     }
 
+    /// Create a config from any serializable value, such as a typed settings struct.
+    /// Its fields are recorded as changes, so `save()` writes all of them.
+    pub fn from_serializable<T: serde::Serialize>(value: &T) -> Result<Config, ConfigError> {
+        let table = match crate::ser::to_value(value)? {
+            Value::Table(table) => table,
+            other => {
+                return Err(ConfigError::Serialize(format!(
+                    "config root must be a table, found {}",
+                    other.kind()
+                )));
+            }
+        };
+        let mut config = Config::builder().build()?;
+        config.keep_defaults();
+        config.layer_sources = table.keys().map(|k| (k.clone(), "set".into())).collect();
+        config.values = table.clone();
+        config.changes = table;
+        Ok(config)
+    }
+
+    /// Whether a top-level key is present
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    /// Whether the config holds no values at all
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
     /// Get a value from config using a key
     pub fn get(&self, key: &str) -> Option<&Value> {
         self.values.get(key)
     }
 
+    /// Get an owned clone of a value from config using a key
+    pub fn get_owned(&self, key: &str) -> Option<Value> {
+        self.get(key).cloned()
+    }
+
+    /// Get a value together with where it came from, the last layer that set it:
+    ///
+    /// - the path of the file that defined it, including files added with `merge_file()`
+    /// - `"extend"` for content merged with `extend_from_str()`
+    /// - `"env"` for env vars and `"override"` for `ConfigBuilder::override_value()`
+    /// - `"changes"` for changes loaded with `load()`
+    /// - `"set"` for `set()`, `set_path()` and `from_serializable()`
+    /// - `"template"` for keys added by `fill_from_template()`
+    /// - `"unknown"` if none of these recorded it
+    pub fn get_with_source(&self, key: &str) -> Option<(&Value, &str)> {
+        let value = self.values.get(key)?;
+        let source = self.source(key).map_or("unknown", |s| s);
+        Some((value, source))
+    }
+
+    /// Where a top-level key came from, the last layer that set it or else the file
+    fn source(&self, key: &str) -> Option<&Arc<str>> {
+        self.layer_sources
+            .get(key)
+            .or_else(|| self.sources.get(key))
+    }
+
+    /// Keys replaced by environment variables during `build()`, as dotted paths for nested keys
+    #[cfg(feature = "env")]
+    pub fn env_overrides(&self) -> Vec<String> {
+        self.env_overrides.clone()
+    }
+
+    /// Get the description of a key, taken from the comment above it in a TOML file.
+    /// Nested keys are looked up by their dotted path
+    pub fn description(&self, key: &str) -> Option<&str> {
+        self.descriptions.get(key).map(|s| s.as_str())
+    }
+
+    /// Get a nested value under a dotted path like `"database.connection.port"`, `None` if a key
+    /// is missing or a value other than a table is reached before the end.
+    /// Keys containing dots can be quoted, like `"\"my.key\".sub"`
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        get_path(&self.values, path)
+    }
+
+    /// Get an owned clone of a nested value under a dotted path, see `get_path()`
+    pub fn get_path_owned(&self, path: &str) -> Option<Value> {
+        self.get_path(path).cloned()
+    }
+
+    /// Get a table from config using a key, `None` if missing or not a table
+    pub fn get_table(&self, key: &str) -> Option<&Table> {
+        self.get(key)?.as_table()
+    }
+
+    /// Get a nested table under a dotted path, `None` if missing or not a table, see `get_path()`
+    pub fn get_table_path(&self, path: &str) -> Option<&Table> {
+        self.get_path(path)?.as_table()
+    }
+
+    /// Deserialize a section under a dotted path (`"database"`, `"services.api"`) into `T`
+    pub fn deserialize_section<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+    ) -> Result<T, ConfigError> {
+        let value = self
+            .get_path(path)
+            .ok_or_else(|| ConfigError::MissingKey(path.to_string()))?;
+        T::deserialize(value.clone())
+    }
+
+    /// Deserialize the whole config into `T`. Missing `Option` fields are `None` and keys
+    /// without a field are ignored, unless `T` opts into `deny_unknown_fields`
+    pub fn try_deserialize<T: serde::de::DeserializeOwned>(&self) -> Result<T, ConfigError> {
+        T::deserialize(Value::Table(self.values.clone()))
+    }
+
+    /// Deserialize the value under a key into `T`, like a nested table into a struct.
+    /// Shorter name for `deserialize_section()`, so dotted paths work too
+    pub fn get_as<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<T, ConfigError> {
+        self.deserialize_section(key)
+    }
+
+    /// Get the first value present in config from a list of keys, useful for renamed keys
+    pub fn get_any(&self, keys: &[&str]) -> Option<&Value> {
+        keys.iter().find_map(|key| self.values.get(*key))
+    }
+
+    /// Get the first value present in config from a list of keys and convert it to `T`
+    pub fn get_any_into<T>(&self, keys: &[&str]) -> Option<Result<T, CannotConvert>>
+    where
+        Value: TryInto<T, Error = CannotConvert>,
+    {
+        self.get_any(keys).map(|value| value.clone().try_into())
+    }
+
     /// Set a value in config changes using a key
     pub fn set(&mut self, key: &str, value: Value) {
+        self.keep_defaults();
         self.changes.insert(key.to_string(), value.clone());
         self.values.insert(key.to_string(), value);
+        self.layer_sources.insert(key.to_string(), "set".into());
     }
 
-    /// List all keys in the config
-    pub fn list(&self) -> Vec<String> {
-        self.values.keys().cloned().collect()
+    /// Set a value under a dotted path like `"server.port"`, creating missing tables on the way.
+    /// Only the value at `path` is recorded as changed, so `save()` leaves out its sibling keys.
+    /// Fails without changing anything if a value other than a table is in the way
+    pub fn set_path(&mut self, path: &str, value: Value) -> Result<(), ConfigError> {
+        let keys = split_path(path);
+        let (last, parents) = keys
+            .split_last()
+            .expect("split_path returns at least one key");
+        self.keep_defaults();
+        let mut table = &mut self.values;
+        for (i, key) in parents.iter().enumerate() {
+            let entry = table
+                .entry(key.clone())
+                .or_insert_with(|| Value::Table(Map::new()));
+            table = match entry {
+                Value::Table(table) => table,
+                other => {
+                    return Err(ConfigError::Convert {
+                        key: keys[..=i].join("."),
+                        error: CannotConvert::new(other.kind().as_str(), "Table"),
+                    });
+                }
+            };
+        }
+        table.insert(last.clone(), value.clone());
+
+        let path: Vec<&str> = keys.iter().map(String::as_str).collect();
+        set_in(&mut self.changes, &path, value);
+        self.layer_sources.insert(keys[0].clone(), "set".into());
+        Ok(())
     }
 
-    /// Load changes to default configuration from `.add_file()` from a file.
+    /// Set a value in config changes using a key, converting it with `Into<Value>`
+    pub fn set_value<V: Into<Value>>(&mut self, key: &str, value: V) {
+        self.set(key, value.into());
+    }
+
+    /// Convert the current values to a `serde_json::Value` object without going through a string
+    #[cfg(feature = "json")]
+    pub fn to_json_value(&self) -> serde_json::Value {
+        serde_json::Value::Object(
+            self.values
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone().into()))
+                .collect(),
+        )
+    }
+
+    /// Validate the current values against a JSON Schema, returning every violation
+    #[cfg(feature = "jsonschema")]
+    pub fn validate_schema(&self, schema: &str) -> Result<(), Vec<String>> {
+        let schema: serde_json::Value =
+            serde_json::from_str(schema).map_err(|e| vec![format!("Invalid schema: {}", e)])?;
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| vec![format!("Invalid schema: {}", e)])?;
+        let instance = self.to_json_value();
+        let errors: Vec<String> = validator
+            .iter_errors(&instance)
+            .map(|e| format!("{}: {}", e.instance_path, e))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parse `content` and deep-merge it into the defaults like one more file added to the builder,
+    /// so env vars, overrides and changes still take precedence over it. See `merge_file()`
+    pub fn extend_from_str(
+        &mut self,
+        format: FileFormat,
+        content: &str,
+    ) -> Result<(), ConfigError> {
+        let parsed = load_map_with(content.to_string(), format, &self.parse_options)?;
+        self.merge_defaults(parsed, "extend".into());
+        Ok(())
+    }
+
+    /// Parse `file` and deep-merge it into the defaults as if it was the last file added to the
+    /// builder, so new nested keys are added and env vars, overrides and changes still take
+    /// precedence over it. The builder's parse options and `ArrayMergePolicy` apply to it.
+    /// Unlike `Config::load()`, its keys are not treated as changes and are not saved.
+    ///
+    /// The values are rebuilt from the layers, so edits made in place with `map_values()`,
+    /// `infer_types()` or `fill_from_template()` are lost, while `set()` values are kept
     #[cfg(feature = "load_after_build")]
-    pub fn load(&mut self, file: File) -> Result<(), String> {
+    pub fn merge_file(&mut self, file: File) -> Result<(), ConfigError> {
         let parsed = file
-            .parse()
-            .map_err(|e| format!("Failed to parse file {}: {}", file.path, e))?;
-        self.changes.extend(parsed);
-        self.values = self.defaults.clone();
-        for (key, value) in self.changes.iter() {
-            if self.values.get(key).is_some() {
-                self.values.insert(key.clone(), value.clone());
+            .parse_with(&self.parse_options)
+            .map_err(|e| ConfigError::parse(&file.path, e))?;
+        self.merge_defaults(parsed, Arc::from(file.path.as_str()));
+        Ok(())
+    }
+
+    fn merge_defaults(&mut self, parsed: Map<String, Value>, source: Arc<str>) {
+        let defaults = self
+            .defaults
+            .get_or_insert_with(|| std::mem::take(&mut self.values));
+        for (key, value) in parsed {
+            self.sources.insert(key.clone(), source.clone());
+            match (defaults.get_mut(&key), value) {
+                (Some(Value::Table(existing)), Value::Table(table)) => merge_deep(existing, table),
+                (_, value) => merge_map(defaults, Map::from_iter([(key, value)]), self.array_merge),
             }
         }
-        Ok(())
+        self.rebuild_values();
     }
 
-    /// Save the current configuration to a file in the specified format
-    pub fn save(&self, format: FileFormat) -> Result<String, String> {
-        save_map(&self.changes, format)
+    /// Stores a copy of the values as the defaults before they are first written over,
+    /// so `rebuild_values()` can start from the files again
+    fn keep_defaults(&mut self) {
+        if self.defaults.is_none() {
+            self.defaults = Some(self.values.clone());
+        }
     }
-}
 
-impl std::fmt::Display for Config {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for (key, val) in self.values.iter() {
-            writeln!(f, "{}: {}", key, val)?;
+    /// Recomputes the values from the defaults and the env, override and changes layers,
+    /// in the order of `ConfigBuilder::build()`
+    fn rebuild_values(&mut self) {
+        if let Some(defaults) = &self.defaults {
+            self.values = defaults.clone();
+        }
+        self.layer_sources.clear();
+        #[cfg(feature = "env")]
+        {
+            self.env_overrides.clear();
+            self.apply_env();
+        }
+        self.apply_overrides();
+        self.apply_changes(self.changes.clone());
+        #[cfg(feature = "intern")]
+        if self.interned {
+            self.intern_strings();
         }
-        Ok(())
     }
-}
 
-fn save_map(_map: &Map<String, Value>, format: FileFormat) -> Result<String, String> {
-    match format {
-        FileFormat::Ini => {
-            #[cfg(feature = "ini")]
-            {
-                Err("Serializing INI format is not supported".to_string())
+    /// Overwrites leaves with env vars, see `ConfigBuilder::env_prefix()`
+    #[cfg(feature = "env")]
+    fn apply_env(&mut self) {
+        for (key, value) in get_env_vars() {
+            let key = key.to_lowercase();
+            let key = match &self.env_prefix {
+                Some(prefix) => match key.strip_prefix(prefix.as_str()) {
+                    Some(key) if prefix.ends_with('_') => key,
+                    Some(key) => match key.strip_prefix('_') {
+                        Some(key) => key,
+                        None => continue,
+                    },
+                    None => continue,
+                },
+                None => key.as_str(),
+            };
+            let separator = match self.env_separator.as_str() {
+                "" => "__",
+                separator => separator,
+            };
+            let mut key_parts: Vec<&str> = key.split(separator).collect();
+            key_parts.retain(|&part| !part.is_empty());
+            let path = key_parts.join(".");
+            let ignored = self.env_ignore.iter().any(|k| {
+                path == *k
+                    || path
+                        .strip_prefix(k.as_str())
+                        .is_some_and(|p| p.starts_with('.'))
+            });
+            if key_parts.is_empty() || ignored {
+                continue;
             }
 
-            #[cfg(not(feature = "ini"))]
-            Err("INI format feature is not enabled".to_string())
-        }
-        FileFormat::Json => {
-            #[cfg(feature = "json")]
-            {
-                Ok(crate::format::json::serialize(_map.clone()))
+            let value = match get_in(&self.values, &key_parts) {
+                Some(slot) if slot.is_table() => continue,
+                Some(slot) => coerce_env(value, slot),
+                None if self.env_prefix.is_some() => Value::String(value),
+                None => continue,
+            };
+            self.keep_defaults();
+            if !insert_in(&mut self.values, &key_parts, value) {
+                continue;
             }
-
-            #[cfg(not(feature = "json"))]
-            Err("JSON format feature is not enabled".to_string())
+            self.layer_sources
+                .insert(key_parts[0].to_string(), "env".into());
+            self.env_overrides.push(path);
         }
-        FileFormat::Yaml => {
-            #[cfg(feature = "yaml")]
-            {
-                Ok(crate::format::yaml::serialize(_map.clone()))
+    }
+
+    /// Sets the values pinned with `ConfigBuilder::override_value()`
+    fn apply_overrides(&mut self) {
+        if !self.overrides.is_empty() {
+            self.keep_defaults();
+        }
+        for (path, value) in &self.overrides {
+            let keys = split_path(path);
+            let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+            self.layer_sources
+                .insert(keys[0].to_string(), "override".into());
+            set_in(&mut self.values, &keys, value.clone());
+        }
+    }
+
+    /// Add every key of `template` missing here, recursing into nested tables.
+    /// Existing values are never replaced, even if their type differs from the template
+    pub fn fill_from_template(&mut self, template: &Config) {
+        self.keep_defaults();
+        for key in fill_in(&mut self.values, &template.values) {
+            self.layer_sources.insert(key, "template".into());
+        }
+    }
+
+    /// Make equal strings share one allocation by turning them into `Value::Shared`.
+    /// Strings added later by `load()` are interned as well
+    #[cfg(feature = "intern")]
+    pub fn intern_strings(&mut self) {
+        self.interned = true;
+        let mut pool = std::collections::HashSet::new();
+        let maps = [&mut self.changes, &mut self.values]
+            .into_iter()
+            .chain(self.defaults.as_mut());
+        for map in maps {
+            map.values_mut().for_each(|v| intern_in(v, &mut pool));
+        }
+    }
+
+    /// Upgrade string values that hold numbers or bools to typed values, see `Value::infer_types`.
+    /// Paths passed to `ConfigBuilder::preserve_string_numbers()` are left as they are
+    pub fn infer_types(&mut self) {
+        self.keep_defaults();
+        let preserved: Vec<(Vec<String>, Value)> = self
+            .parse_options
+            .preserve_strings
+            .iter()
+            .filter_map(|path| Some((split_path(path), get_path(&self.values, path)?.clone())))
+            .collect();
+        self.values.values_mut().for_each(Value::infer_types);
+        for (keys, value) in preserved {
+            let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+            set_in(&mut self.values, &keys, value);
+        }
+    }
+
+    /// Replace every leaf value (anything but tables and arrays) with `f(leaf)`, recursing
+    /// through nested tables and arrays. Changes to be saved are left untouched.
+    pub fn map_values(&mut self, mut f: impl FnMut(&Value) -> Value) {
+        self.keep_defaults();
+        for value in self.values.values_mut() {
+            map_leaves(value, &mut f);
+        }
+    }
+
+    /// Remove a value under a dotted path (`"server.tls.cert"`) from values, defaults, changes
+    /// and overrides, returning the removed value. It stays removed after `merge_file()`
+    /// unless the merged file sets it again
+    pub fn remove_path(&mut self, path: &str) -> Option<Value> {
+        self.remove_path_with(path, false)
+    }
+
+    /// Like `remove_path`, but also removes parent tables left empty by the removal
+    pub fn remove_path_prune(&mut self, path: &str) -> Option<Value> {
+        self.remove_path_with(path, true)
+    }
+
+    fn remove_path_with(&mut self, path: &str, prune: bool) -> Option<Value> {
+        let keys = split_path(path);
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        self.keep_defaults();
+        if let Some(defaults) = &mut self.defaults {
+            remove_in(defaults, &keys, prune);
+        }
+        remove_in(&mut self.changes, &keys, prune);
+        self.overrides.retain(|(path, _)| {
+            let path = split_path(path);
+            !(path.len() >= keys.len() && path.iter().zip(&keys).all(|(a, b)| a == b))
+        });
+        let removed = remove_in(&mut self.values, &keys, prune);
+        if !self.values.contains_key(keys[0]) {
+            self.sources.shift_remove(keys[0]);
+            self.layer_sources.shift_remove(keys[0]);
+        }
+        removed
+    }
+
+    /// Non-fatal warnings collected while building the config
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Base directory set with `ConfigBuilder::base_dir()`
+    pub fn base_dir(&self) -> Option<&Path> {
+        self.base_dir.as_deref()
+    }
+
+    /// Resolve a path against the base directory, absolute paths are returned as is
+    pub fn resolve_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        match &self.base_dir {
+            Some(base_dir) => base_dir.join(path),
+            None => path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Get a string value as a path, resolving relative paths against the base directory
+    pub fn get_path_buf(&self, key: &str) -> Result<PathBuf, ConfigError> {
+        match self.get(key).map(|value| value.as_str().ok_or(value)) {
+            Some(Ok(path)) => Ok(self.resolve_path(path)),
+            Some(Err(value)) => Err(ConfigError::Convert {
+                key: key.to_string(),
+                error: CannotConvert::new(value.kind().as_str(), "Path"),
+            }),
+            None => Err(ConfigError::MissingKey(key.to_string())),
+        }
+    }
+
+    /// Get a `host:port` string as a socket address, like `127.0.0.1:8080` or `[::1]:80`
+    pub fn get_socket_addr(&self, key: &str) -> Result<SocketAddr, ConfigError> {
+        let addr = self.get_address_str(key)?;
+        addr.parse().map_err(|error| ConfigError::InvalidAddress {
+            key: key.to_string(),
+            value: addr.to_string(),
+            error,
+        })
+    }
+
+    /// Get a socket address from an IP under `host_key` and a port under `port_key`.
+    /// Host names like `localhost` are not resolved and fail to parse
+    pub fn get_socket_addr_parts(
+        &self,
+        host_key: &str,
+        port_key: &str,
+    ) -> Result<SocketAddr, ConfigError> {
+        let host = self.get_address_str(host_key)?;
+        let ip: IpAddr = host.parse().map_err(|error| ConfigError::InvalidAddress {
+            key: host_key.to_string(),
+            value: host.to_string(),
+            error,
+        })?;
+        let port = self.get_i64_in_range(port_key, 0, u16::MAX as i64)?;
+        Ok(SocketAddr::new(ip, port as u16))
+    }
+
+    /// Get an integer range written as `"start..end"`, `"start..=end"` or a `[start, end]` array,
+    /// which is read like `start..end`
+    pub fn get_range(&self, key: &str) -> Result<Range<i64>, ConfigError> {
+        let value = self
+            .get(key)
+            .ok_or_else(|| ConfigError::MissingKey(key.to_string()))?;
+        let invalid = |reason: &str| ConfigError::InvalidRange {
+            key: key.to_string(),
+            value: value.to_string(),
+            reason: reason.to_string(),
+        };
+        let malformed = || invalid("expected `start..end`, `start..=end` or `[start, end]`");
+        let (start, end, inclusive) = match value {
+            Value::Array(bounds) => match bounds.as_slice() {
+                [Value::Int(start), Value::Int(end)] => (*start, *end, false),
+                _ => return Err(malformed()),
+            },
+            value => {
+                let text = value.as_str().ok_or_else(malformed)?;
+                let (start, end) = text.split_once("..").ok_or_else(malformed)?;
+                let (end, inclusive) = match end.strip_prefix('=') {
+                    Some(end) => (end, true),
+                    None => (end, false),
+                };
+                let start = start.trim().parse().map_err(|_| malformed())?;
+                let end = end.trim().parse().map_err(|_| malformed())?;
+                (start, end, inclusive)
             }
+        };
+        if start > end {
+            return Err(invalid("start is greater than end"));
+        }
+        let end = match inclusive {
+            true => end
+                .checked_add(1)
+                .ok_or_else(|| invalid("end is out of range"))?,
+            false => end,
+        };
+        Ok(start..end)
+    }
 
-            #[cfg(not(feature = "yaml"))]
-            Err("YAML format feature is not enabled".to_string())
+    fn get_address_str(&self, key: &str) -> Result<&str, ConfigError> {
+        match self.get(key).map(|value| value.as_str().ok_or(value)) {
+            Some(Ok(addr)) => Ok(addr),
+            Some(Err(value)) => Err(ConfigError::Convert {
+                key: key.to_string(),
+                error: CannotConvert::new(value.kind().as_str(), "SocketAddr"),
+            }),
+            None => Err(ConfigError::MissingKey(key.to_string())),
         }
-        FileFormat::Toml => {
-            #[cfg(feature = "toml")]
-            {
-                Ok(crate::format::toml::serialize(_map.clone()))
+    }
+
+    /// Get a bool value, coercing numbers and strings like `"yes"`/`"off"` with `Value::coerce_bool`
+    pub fn get_bool(&self, key: &str) -> Result<bool, ConfigError> {
+        self.get(key)
+            .ok_or_else(|| ConfigError::MissingKey(key.to_string()))?
+            .coerce_bool()
+            .map_err(|error| ConfigError::Convert {
+                key: key.to_string(),
+                error,
+            })
+    }
+
+    /// Get a value converted to a string, numbers and bools are formatted
+    pub fn get_string(&self, key: &str) -> Result<String, ConfigError> {
+        self.get_converted(key)
+    }
+
+    /// Get a value converted to an integer, floats are truncated and strings are parsed
+    pub fn get_int(&self, key: &str) -> Result<i64, ConfigError> {
+        self.get_converted(key)
+    }
+
+    /// Get a value converted to a float, integers are widened and strings are parsed, with a
+    /// decimal comma if `ConfigBuilder::decimal_comma()` is enabled
+    pub fn get_float(&self, key: &str) -> Result<f64, ConfigError> {
+        if !self.decimal_comma {
+            return self.get_converted(key);
+        }
+        let value = self
+            .get(key)
+            .ok_or_else(|| ConfigError::MissingKey(key.to_string()))?;
+        decimal_comma_to_dot(value.clone())
+            .try_into()
+            .map_err(|error| ConfigError::Convert {
+                key: key.to_string(),
+                error,
+            })
+    }
+
+    /// Get an integer value that must be within `min..=max`
+    pub fn get_i64_in_range(&self, key: &str, min: i64, max: i64) -> Result<i64, ConfigError> {
+        self.get_in_range(key, min, max, Config::get_int)
+    }
+
+    /// Get an integer value, clamped to `min..=max` instead of erroring when out of range.
+    /// Fails with `ConfigError::InvalidRange` if `min` is greater than `max`
+    pub fn get_i64_clamped(&self, key: &str, min: i64, max: i64) -> Result<i64, ConfigError> {
+        self.get_clamped(key, min, max, Config::get_int)
+    }
+
+    /// Get a float value that must be within `min..=max`
+    pub fn get_f64_in_range(&self, key: &str, min: f64, max: f64) -> Result<f64, ConfigError> {
+        self.get_in_range(key, min, max, Config::get_float)
+    }
+
+    /// Get a float value, clamped to `min..=max` instead of erroring when out of range.
+    /// Fails with `ConfigError::InvalidRange` if `min` is greater than `max` or either is NaN
+    pub fn get_f64_clamped(&self, key: &str, min: f64, max: f64) -> Result<f64, ConfigError> {
+        self.get_clamped(key, min, max, Config::get_float)
+    }
+
+    fn get_clamped<T>(
+        &self,
+        key: &str,
+        min: T,
+        max: T,
+        get: fn(&Config, &str) -> Result<T, ConfigError>,
+    ) -> Result<T, ConfigError>
+    where
+        T: PartialOrd + std::fmt::Display,
+    {
+        // Bounds are unordered when one is NaN, which `f64::clamp` would panic on
+        let reason = match min.partial_cmp(&max) {
+            Some(std::cmp::Ordering::Greater) => Some("min is greater than max"),
+            None => Some("bounds must not be NaN"),
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            return Err(ConfigError::InvalidRange {
+                key: key.to_string(),
+                value: format!("{}..={}", min, max),
+                reason: reason.to_string(),
+            });
+        }
+        let value = get(self, key)?;
+        Ok(if value < min {
+            min
+        } else if value > max {
+            max
+        } else {
+            value
+        })
+    }
+
+    fn get_in_range<T>(
+        &self,
+        key: &str,
+        min: T,
+        max: T,
+        get: fn(&Config, &str) -> Result<T, ConfigError>,
+    ) -> Result<T, ConfigError>
+    where
+        T: PartialOrd + std::fmt::Display,
+    {
+        let value = get(self, key)?;
+        if value < min || value > max {
+            return Err(ConfigError::OutOfRange {
+                key: key.to_string(),
+                value: value.to_string(),
+                min: min.to_string(),
+                max: max.to_string(),
+            });
+        }
+        Ok(value)
+    }
+
+    fn get_converted<T>(&self, key: &str) -> Result<T, ConfigError>
+    where
+        Value: TryInto<T, Error = CannotConvert>,
+    {
+        let value = self
+            .get_owned(key)
+            .ok_or_else(|| ConfigError::MissingKey(key.to_string()))?;
+        value.try_into().map_err(|error| ConfigError::Convert {
+            key: key.to_string(),
+            error,
+        })
+    }
+
+    /// Get a string value that must be one of the allowed variants
+    pub fn get_enum(&self, key: &str, allowed: &[&str]) -> Result<String, ConfigError> {
+        self.find_enum(key, allowed, |a, b| a == b)
+    }
+
+    /// Get a string value that must be one of the allowed variants, ignoring case. Returns
+    /// the variant as spelled in `allowed`.
+    pub fn get_enum_ignore_case(&self, key: &str, allowed: &[&str]) -> Result<String, ConfigError> {
+        self.find_enum(key, allowed, |a, b| a.eq_ignore_ascii_case(b))
+    }
+
+    fn find_enum(
+        &self,
+        key: &str,
+        allowed: &[&str],
+        matches: impl Fn(&str, &str) -> bool,
+    ) -> Result<String, ConfigError> {
+        let value = match self.get(key).map(|value| value.as_str().ok_or(value)) {
+            Some(Ok(value)) => value,
+            Some(Err(value)) => {
+                return Err(ConfigError::Convert {
+                    key: key.to_string(),
+                    error: CannotConvert::new(value.kind().as_str(), "String"),
+                });
             }
+            None => return Err(ConfigError::MissingKey(key.to_string())),
+        };
+        allowed
+            .iter()
+            .find(|variant| matches(variant, value))
+            .map(|variant| variant.to_string())
+            .ok_or_else(|| ConfigError::InvalidVariant {
+                key: key.to_string(),
+                value: value.to_string(),
+                allowed: allowed.iter().map(|a| a.to_string()).collect(),
+            })
+    }
 
-            #[cfg(not(feature = "toml"))]
-            Err("TOML format feature is not enabled".to_string())
+    /// List all keys in the config
+    pub fn list(&self) -> Vec<String> {
+        self.values.keys().cloned().collect()
+    }
+
+    /// Top-level entries sorted by key, unlike `list()` which keeps insertion order
+    pub fn entries_sorted(&self) -> Vec<(String, &Value)> {
+        let mut entries: Vec<(String, &Value)> = self
+            .values
+            .iter()
+            .map(|(key, value)| (key.clone(), value))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Every non-table value (and empty table) under its dotted path, sorted by path
+    pub fn entries_flat_sorted(&self) -> Vec<(String, &Value)> {
+        let mut entries = Vec::new();
+        flatten_into("", &self.values, &mut entries);
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Every leaf of the given kind under its dotted path, sorted by path like
+    /// `entries_flat_sorted()`
+    pub fn collect_by_kind(&self, kind: ValueKind) -> Vec<(String, &Value)> {
+        let mut entries = self.entries_flat_sorted();
+        entries.retain(|(_, value)| value.kind() == kind);
+        entries
+    }
+
+    /// Panics with a diff of every differing path unless both configs hold the same values.
+    /// Paths in `ignore_paths` and everything nested under them are skipped
+    #[track_caller]
+    pub fn assert_eq_ignoring(&self, other: &Config, ignore_paths: &[&str]) {
+        let ignored = |path: &str| {
+            ignore_paths.iter().any(|ignore| {
+                path.strip_prefix(ignore)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+        };
+        let flat = |config: &Config| -> Map<String, Value> {
+            let mut entries = Vec::new();
+            flatten_into("", &config.values, &mut entries);
+            entries
+                .into_iter()
+                .filter(|(path, _)| !ignored(path))
+                .map(|(path, value)| (path, value.clone()))
+                .collect()
+        };
+        let (left, right) = (flat(self), flat(other));
+
+        let mut diff = Vec::new();
+        for (path, value) in left.iter() {
+            match right.get(path) {
+                Some(other) if other == value => {}
+                Some(other) => diff.push(format!("  {}: {} != {}", path, value, other)),
+                None => diff.push(format!("  {}: {} != <missing>", path, value)),
+            }
         }
-        FileFormat::Ron => {
-            #[cfg(feature = "ron")]
-            {
-                Ok(crate::format::ron::serialize(_map.clone()))
+        for (path, value) in right.iter() {
+            if !left.contains_key(path) {
+                diff.push(format!("  {}: <missing> != {}", path, value));
             }
+        }
+        if !diff.is_empty() {
+            panic!("Configs differ:\n{}", diff.join("\n"));
+        }
+    }
 
-            #[cfg(not(feature = "ron"))]
-            Err("RON format feature is not enabled".to_string())
+    /// Load changes to default configuration from `.add_file()` from a file.
+    /// Nested tables are merged, so only the leaves present in the file are changed, while
+    /// arrays and other values replace what was there. The changes are applied on top of the
+    /// current values, so leaves they don't touch keep their env vars, overrides and edits
+    /// like `infer_types()`.
+    #[cfg(feature = "load_after_build")]
+    pub fn load(&mut self, file: File) -> Result<(), ConfigError> {
+        let parsed = file
+            .parse_with(&self.parse_options)
+            .map_err(|e| ConfigError::parse(&file.path, e))?;
+        merge_deep(&mut self.changes, parsed.clone());
+        self.apply_changes(parsed);
+        #[cfg(feature = "intern")]
+        if self.interned {
+            self.intern_strings();
+        }
+        Ok(())
+    }
+
+    /// Deep-merges `changes` into the values as the last layer, see `ConfigBuilder::build()`.
+    /// Unless new keys are allowed, keys missing from the values are skipped at every level
+    /// with a warning
+    fn apply_changes(&mut self, changes: Map<String, Value>) {
+        let mut ignored = Vec::new();
+        for (key, value) in changes {
+            self.keep_defaults();
+            let change = Map::from_iter([(key.clone(), value)]);
+            if merge_changes(
+                &mut self.values,
+                change,
+                self.allow_new_keys,
+                "",
+                &mut ignored,
+            ) {
+                self.layer_sources.insert(key, "changes".into());
+            }
+        }
+        for path in ignored {
+            let warning = Warning::IgnoredChange(path);
+            if !self.warnings.contains(&warning) {
+                self.warnings.push(warning);
+            }
+        }
+    }
+
+    /// Every key and value like `Display` prints them, including the ones marked secret
+    pub fn to_string_unredacted(&self) -> String {
+        DisplayValues {
+            config: self,
+            redact: false,
+        }
+        .to_string()
+    }
+
+    /// Take a snapshot of the current defaults, overrides, changes and values, to be restored later
+    pub fn snapshot(&self) -> ConfigSnapshot {
+        ConfigSnapshot {
+            defaults: self.defaults.clone(),
+            overrides: self.overrides.clone(),
+            #[cfg(feature = "env")]
+            env_overrides: self.env_overrides.clone(),
+            changes: self.changes.clone(),
+            values: self.values.clone(),
+            sources: self.sources.clone(),
+            layer_sources: self.layer_sources.clone(),
+        }
+    }
+
+    /// Roll back the config to a snapshot taken with `Config::snapshot()`, including files merged
+    /// since with `merge_file()`, so they don't come back on the next merge
+    pub fn restore(&mut self, snapshot: ConfigSnapshot) {
+        self.defaults = snapshot.defaults;
+        self.overrides = snapshot.overrides;
+        #[cfg(feature = "env")]
+        {
+            self.env_overrides = snapshot.env_overrides;
+        }
+        self.changes = snapshot.changes;
+        self.values = snapshot.values;
+        self.sources = snapshot.sources;
+        self.layer_sources = snapshot.layer_sources;
+    }
+
+    /// Save the current configuration to a file in the specified format. Only changes made with
+    /// `set()` or `load()` are written, to be loaded back on top of the same files later
+    pub fn save(&self, format: FileFormat) -> Result<String, ConfigError> {
+        save_map_with(&self.changes, format, &self.save_options)
+    }
+
+    /// Save every value in effect, defaults from files included, like a standalone config.
+    /// Unlike `save()` this also writes values set by env vars and `override_value()`
+    pub fn save_full(&self, format: FileFormat) -> Result<String, ConfigError> {
+        save_map_with(&self.values, format, &self.save_options)
+    }
+
+    /// Save changes in the format set with `ConfigBuilder::save_format()`
+    pub fn save_default(&self) -> Result<String, ConfigError> {
+        let format = self
+            .save_options
+            .format
+            .clone()
+            .ok_or(ConfigError::NoSaveFormat)?;
+        self.save(format)
+    }
+}
+
+/// Saved state of a `Config`, created with `Config::snapshot()`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigSnapshot {
+    defaults: Option<Map<String, Value>>,
+    overrides: Vec<(String, Value)>,
+    #[cfg(feature = "env")]
+    env_overrides: Vec<String>,
+    changes: Map<String, Value>,
+    values: Map<String, Value>,
+    sources: Map<String, Arc<str>>,
+    layer_sources: Map<String, Arc<str>>,
+}
+
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        DisplayValues {
+            config: self,
+            redact: true,
+        }
+        .fmt(f)
+    }
+}
+
+/// Writes every key and value of a `Config`, printing `***` for secrets when `redact` is set
+struct DisplayValues<'a> {
+    config: &'a Config,
+    redact: bool,
+}
+
+impl std::fmt::Display for DisplayValues<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secrets: Vec<Vec<String>> = if self.redact {
+            self.config.secrets.iter().map(|s| split_path(s)).collect()
+        } else {
+            Vec::new()
+        };
+        let mut path = Vec::new();
+        for (key, val) in self.config.values.iter() {
+            write!(f, "{}: ", key)?;
+            path.push(key.as_str());
+            fmt_redacted(f, &mut path, val, &secrets)?;
+            path.pop();
+            writeln!(f)?;
         }
+        Ok(())
     }
 }
 
-fn load_map(save: String, format: FileFormat) -> Result<Map<String, Value>, String> {
-    if save.is_empty() {
-        return Err("Empty content".to_string());
+/// Writes `value` like its `Display` does, but prints `***` for `path` and anything under it
+/// that is listed in `secrets`. Array elements share the path of their array, so `a.b` also
+/// matches `b` in every table of the array `a`
+fn fmt_redacted<'a>(
+    f: &mut std::fmt::Formatter<'_>,
+    path: &mut Vec<&'a str>,
+    value: &'a Value,
+    secrets: &[Vec<String>],
+) -> std::fmt::Result {
+    if secrets.iter().any(|s| s.iter().eq(path.iter())) {
+        return write!(f, "***");
+    }
+    let nested = secrets
+        .iter()
+        .any(|s| s.len() > path.len() && s.iter().zip(path.iter()).all(|(a, b)| a == b));
+    match value {
+        Value::Table(table) if nested => {
+            write!(f, "{{")?;
+            for (i, (key, value)) in table.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "({}: ", key)?;
+                path.push(key.as_str());
+                fmt_redacted(f, path, value, secrets)?;
+                path.pop();
+                write!(f, ")")?;
+            }
+            write!(f, "}}")
+        }
+        Value::Array(array) if nested => {
+            write!(f, "[")?;
+            for (i, value) in array.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                fmt_redacted(f, path, value, secrets)?;
+            }
+            write!(f, "]")
+        }
+        _ => write!(f, "{}", value),
     }
+}
+
+/// Converts configuration content from one format to another.
+/// ```rust
+/// #[cfg(all(feature = "json", feature = "yaml"))]
+/// {
+/// use ronf::FileFormat;
+/// let yaml = ronf::convert("{\"key\": \"value\"}", FileFormat::Json, FileFormat::Yaml).unwrap();
+/// assert_eq!(yaml, "---\nkey: value");
+/// }
+/// ```
+pub fn convert(content: &str, from: FileFormat, to: FileFormat) -> Result<String, ConfigError> {
+    let map = load_map(content.to_string(), from)?;
+    save_map(&map, to)
+}
 
+fn save_map(map: &Map<String, Value>, format: FileFormat) -> Result<String, ConfigError> {
+    save_map_with(map, format, &SaveOptions::default())
+}
+
+fn save_map_with(
+    _map: &Map<String, Value>,
+    format: FileFormat,
+    _options: &SaveOptions,
+) -> Result<String, ConfigError> {
     match format {
         FileFormat::Ini => {
             #[cfg(feature = "ini")]
             {
-                crate::format::ini::deserialize(save.clone())
+                crate::format::ini::serialize(_map.clone())
+                    .map_err(|message| format_error(FileFormat::Ini, message))
             }
 
             #[cfg(not(feature = "ini"))]
-            Err("INI format feature is not enabled".to_string())
+            Err(ConfigError::FeatureDisabled(FileFormat::Ini))
         }
         FileFormat::Json => {
             #[cfg(feature = "json")]
             {
-                crate::format::json::deserialize(save.clone())
+                crate::format::json::serialize(_map.clone())
+                    .map_err(|message| format_error(FileFormat::Json, message))
             }
 
             #[cfg(not(feature = "json"))]
-            Err("JSON format feature is not enabled".to_string())
+            Err(ConfigError::FeatureDisabled(FileFormat::Json))
         }
         FileFormat::Yaml => {
             #[cfg(feature = "yaml")]
             {
-                crate::format::yaml::deserialize(save.clone())
+                Ok(crate::format::yaml::serialize(
+                    _map.clone(),
+                    _options.yaml_style,
+                    _options.yaml_document_marker,
+                ))
             }
 
             #[cfg(not(feature = "yaml"))]
-            Err("YAML format feature is not enabled".to_string())
+            Err(ConfigError::FeatureDisabled(FileFormat::Yaml))
         }
         FileFormat::Toml => {
             #[cfg(feature = "toml")]
             {
-                crate::format::toml::deserialize(save.clone())
+                crate::format::toml::serialize(_map.clone(), _options.toml_inline_tables)
+                    .map_err(|message| format_error(FileFormat::Toml, message))
             }
 
             #[cfg(not(feature = "toml"))]
-            Err("TOML format feature is not enabled".to_string())
+            Err(ConfigError::FeatureDisabled(FileFormat::Toml))
         }
         FileFormat::Ron => {
             #[cfg(feature = "ron")]
             {
-                crate::format::ron::deserialize(save.clone())
+                Ok(crate::format::ron::serialize(_map.clone()))
             }
 
             #[cfg(not(feature = "ron"))]
-            Err("RON format feature is not enabled".to_string())
+            Err(ConfigError::FeatureDisabled(FileFormat::Ron))
         }
     }
 }
 
+fn load_map(save: String, format: FileFormat) -> Result<Map<String, Value>, ConfigError> {
+    load_map_with(save, format, &ParseOptions::default())
+}
+
+fn load_map_with(
+    save: String,
+    format: FileFormat,
+    options: &ParseOptions,
+) -> Result<Map<String, Value>, ConfigError> {
+    parse_content(save, &format, options)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -329,264 +1909,3116 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_config_set_value() {
+        let mut config = Config::builder().build().unwrap();
+        config.set_value("int", 5);
+        config.set_value("string", "hello");
+        config.set_value("bool", true);
+        assert_eq!(config.get("int").unwrap(), &Value::Int(5));
+        assert_eq!(
+            config.get("string").unwrap(),
+            &Value::String("hello".to_string())
+        );
+        assert_eq!(config.get("bool").unwrap(), &Value::Bool(true));
+    }
+
     #[test]
     #[cfg(feature = "json")]
-    fn test_config_list() {
-        let config = Config::builder()
+    fn test_config_get_owned() {
+        let mut config = Config::builder()
             .add_file(File::new_str(
                 "test_file",
                 FileFormat::Json,
-                "{\"key3\": \"value\"}",
+                "{\"counter\": 1, \"table\": {\"nested\": \"value\"}}",
             ))
             .build()
             .unwrap();
-        assert_eq!(config.list(), vec!["key3".to_string()]);
-    }
-
-    #[cfg(feature = "json")]
-    mod config_display {
-        use super::*;
-        use std::fmt::{self, Write};
-
-        #[test]
-        fn test_config_display() {
-            let config = Config::builder()
-                .add_file(File::new_str(
-                    "test_file",
-                    FileFormat::Json,
-                    "{\"key3_2\": \"value\"}",
-                ))
-                .build()
-                .unwrap();
-
-            let mut output = String::new();
-            let result = write!(&mut output, "{}", config);
-
-            assert!(result.is_ok());
-            assert_eq!(output, "key3_2: \"value\"\n");
-        }
-
-        struct FailingWriter;
-
-        impl Write for FailingWriter {
-            fn write_str(&mut self, _s: &str) -> fmt::Result {
-                Err(fmt::Error) // Simulate a write failure
-            }
-        }
-
-        #[test]
-        fn test_config_display_write_error() {
-            let config = Config::builder()
-                .add_file(File::new_str(
-                    "test_file",
-                    FileFormat::Json,
-                    "{\"key3_2\": \"value\"}",
-                ))
-                .build()
-                .unwrap();
-
-            let mut failing_writer = FailingWriter;
-            let result = write!(&mut failing_writer, "{}", config);
-
-            assert!(result.is_err()); // Ensure that write errors propagate
-        }
+        let counter: i64 = config.get_owned("counter").unwrap().try_into().unwrap();
+        config.set("counter", Value::Int(counter + 1));
+        assert_eq!(config.get("counter").unwrap(), &Value::Int(2));
+        assert_eq!(
+            config.get_path_owned("table.nested"),
+            Some(Value::String("value".to_string()))
+        );
+        assert!(config.get_path_owned("table.missing").is_none());
+        assert!(config.get_path_owned("").is_none());
     }
 
     #[test]
-    #[cfg(feature = "load_after_build")]
     #[cfg(feature = "json")]
-    fn test_config_load() {
-        let mut config = Config::builder()
+    fn test_config_get_table() {
+        let config = Config::builder()
             .add_file(File::new_str(
                 "test_file",
                 FileFormat::Json,
-                "{\"key4\": \"value\"}",
+                "{\"server\": {\"host\": \"localhost\", \"port\": 80, \"tls\": {\"enabled\": true}}, \"key\": 1}",
             ))
             .build()
             .unwrap();
-        config
-            .load(File::new_str(
+        let server = config.get_table("server").unwrap();
+        let keys: Vec<&String> = server.keys().collect();
+        assert_eq!(keys, vec!["host", "port", "tls"]);
+        let tls = config.get_table_path("server.tls").unwrap();
+        assert_eq!(tls.get("enabled").unwrap(), &Value::Bool(true));
+        assert!(config.get_table("key").is_none());
+        assert!(config.get_table("missing").is_none());
+        assert!(config.get_table_path("server.host").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_get_any() {
+        let config = Config::builder()
+            .add_file(File::new_str(
                 "test_file",
                 FileFormat::Json,
-                "{\"key4\": \"new_value\", \"key5\": \"another_value\"}",
+                "{\"old_name\": 42}",
             ))
+            .build()
             .unwrap();
         assert_eq!(
-            config.get("key4").unwrap(),
-            &Value::String("new_value".to_string())
+            config.get_any(&["new_name", "old_name"]).unwrap(),
+            &Value::Int(42)
+        );
+        assert_eq!(
+            config.get_any_into::<i64>(&["new_name", "old_name"]),
+            Some(Ok(42))
         );
+        assert!(config.get_any(&["new_name", "other_name"]).is_none());
+    }
 
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_builder_deprecate() {
         let config = Config::builder()
             .add_file(File::new_str(
                 "test_file",
                 FileFormat::Json,
-                "{\"key6\": \"value\"}",
+                "{\"old_key\": \"value\", \"gone_key\": 1}",
             ))
+            .deprecate("old_key", Some("new_key"))
+            .deprecate("gone_key", None)
+            .deprecate("missing_key", None)
             .build()
-            .unwrap()
-            .load(File::new_str(
-                "test_file",
-                FileFormat::Json,
-                "{\"key6\": \"new_value}",
-            ));
-        assert!(config.is_err());
+            .unwrap();
+        assert_eq!(
+            config.warnings(),
+            &[
+                Warning::Deprecated {
+                    key: "old_key".to_string(),
+                    replacement: Some("new_key".to_string()),
+                },
+                Warning::Deprecated {
+                    key: "gone_key".to_string(),
+                    replacement: None,
+                },
+            ]
+        );
+        assert_eq!(
+            config.get("new_key").unwrap(),
+            &Value::String("value".to_string())
+        );
     }
 
     #[test]
     #[cfg(feature = "json")]
-    fn test_config_save() {
+    fn test_config_snapshot_restore() {
         let mut config = Config::builder()
             .add_file(File::new_str(
                 "test_file",
                 FileFormat::Json,
-                "{\"key7\": \"value\"}",
+                "{\"key\": \"value\"}",
             ))
             .build()
             .unwrap();
-        config.set("key7", Value::String("new_value".to_string()));
-        let save = config.save(FileFormat::Json).unwrap();
-        assert_eq!(save, "{\"key7\":\"new_value\"}");
+        let snapshot = config.snapshot();
+        config.set("key", Value::String("new_value".to_string()));
+        config.set("other", Value::Int(1));
+        config.restore(snapshot);
+        assert_eq!(
+            config.get("key").unwrap(),
+            &Value::String("value".to_string())
+        );
+        assert!(config.get("other").is_none());
+        assert_eq!(config.save(FileFormat::Json).unwrap(), "{}");
     }
 
     #[test]
-    fn test_builder_failed_parse_file() {
-        let config = Config::builder()
-            .add_file(File::new_str(
-                "test_file",
-                FileFormat::Json,
-                "{\"key8\": \"value}",
-            ))
-            .build();
-        assert!(config.is_err());
+    #[cfg(all(feature = "load_after_build", feature = "json"))]
+    fn test_config_restore_then_merge() {
+        let mut config = Config::builder()
+            .add_file(File::new_str("a.json", FileFormat::Json, r#"{"a": 1}"#))
+            .override_value("o", 1)
+            .build()
+            .unwrap();
+        let snapshot = config.snapshot();
+        config
+            .merge_file(File::new_str("b.json", FileFormat::Json, r#"{"b": 2}"#))
+            .unwrap();
+        config.remove_path("o");
+        config.restore(snapshot);
+        assert_eq!(config.get("b"), None);
+        config
+            .merge_file(File::new_str("c.json", FileFormat::Json, r#"{"c": 3}"#))
+            .unwrap();
+        assert_eq!(config.get("a"), Some(&Value::Int(1)));
+        assert_eq!(config.get("b"), None);
+        assert_eq!(config.get("c"), Some(&Value::Int(3)));
+        assert_eq!(
+            config.get_with_source("o"),
+            Some((&Value::Int(1), "override"))
+        );
+    }
+
+    #[test]
+    fn test_config_base_dir() {
+        let config = Config::builder().base_dir("/etc/app").build().unwrap();
+        assert_eq!(config.base_dir(), Some(Path::new("/etc/app")));
+        assert_eq!(
+            config.resolve_path("include/extra.json"),
+            PathBuf::from("/etc/app/include/extra.json")
+        );
+        assert_eq!(
+            config.resolve_path("/var/log/app"),
+            PathBuf::from("/var/log/app")
+        );
+
+        let config = Config::builder().build().unwrap();
+        assert_eq!(config.base_dir(), None);
+        assert_eq!(
+            config.resolve_path("include/extra.json"),
+            PathBuf::from("include/extra.json")
+        );
     }
 
     #[test]
     #[cfg(feature = "json")]
-    fn test_builder_load() {
+    fn test_config_get_path_buf() {
         let config = Config::builder()
             .add_file(File::new_str(
                 "test_file",
                 FileFormat::Json,
-                "{\"key9\": \"value\"}",
-            ))
-            .load(File::new_str(
-                "test_file",
-                FileFormat::Json,
-                "{\"key9\": \"new_value\"}",
+                "{\"log_dir\": \"logs\", \"data_dir\": \"/var/data\", \"port\": 80}",
             ))
-            .unwrap()
+            .base_dir("/etc/app")
             .build()
             .unwrap();
         assert_eq!(
-            config.get("key9").unwrap(),
-            &Value::String("new_value".to_string())
+            config.get_path_buf("log_dir"),
+            Ok(PathBuf::from("/etc/app/logs"))
+        );
+        assert_eq!(
+            config.get_path_buf("data_dir"),
+            Ok(PathBuf::from("/var/data"))
+        );
+        assert_eq!(
+            config.get_path_buf("cache_dir"),
+            Err(ConfigError::MissingKey("cache_dir".to_string()))
+        );
+        assert_eq!(
+            config.get_path_buf("port"),
+            Err(ConfigError::Convert {
+                key: "port".to_string(),
+                error: CannotConvert::new("Int", "Path"),
+            })
         );
     }
 
     #[test]
     #[cfg(feature = "json")]
-    fn test_builder_load_failure() {
+    fn test_builder_expect() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            "{\"port\": \"80\", \"server\": {\"host\": 1, \"name\": \"app\"}}",
+        );
         let config = Config::builder()
-            .add_file(File::new_str(
-                "test_file",
-                FileFormat::Json,
-                "{\"key10\": \"value\"}",
-            ))
-            .load(File::new_str("test_file", FileFormat::Json, ""));
-        assert!(config.is_err());
+            .add_file(file.clone())
+            .expect("port", ValueKind::Int)
+            .expect("server.host", ValueKind::String)
+            .expect("server.name", ValueKind::String)
+            .expect("missing", ValueKind::Bool)
+            .build();
+        assert_eq!(
+            config.err().unwrap(),
+            ConfigError::TypeMismatch(vec![
+                "port expected Int, found String".to_string(),
+                "server.host expected String, found Int".to_string(),
+            ])
+        );
+
+        let config = Config::builder()
+            .add_file(file)
+            .expect("port", ValueKind::String)
+            .expect("server", ValueKind::Table)
+            .build();
+        assert!(config.is_ok());
     }
 
     #[test]
     #[cfg(feature = "json")]
-    fn test_builder_load_none() {
+    fn test_config_get_enum() {
         let config = Config::builder()
             .add_file(File::new_str(
                 "test_file",
                 FileFormat::Json,
-                "{\"key11\": \"value\"}",
-            ))
-            .load(File::new_str(
-                "test_file",
-                FileFormat::Json,
-                "{\"key12\": \"new_value\"}",
+                "{\"log_level\": \"debug\", \"mode\": \"FAST\", \"level\": 1}",
             ))
-            .unwrap()
             .build()
             .unwrap();
+        let levels = ["debug", "info", "warn"];
         assert_eq!(
-            config.get("key11").unwrap(),
-            &Value::String("value".to_string())
+            config.get_enum("log_level", &levels),
+            Ok("debug".to_string())
         );
-        assert!(config.get("key12").is_none());
+        assert_eq!(
+            config.get_enum("mode", &["fast", "slow"]),
+            Err(ConfigError::InvalidVariant {
+                key: "mode".to_string(),
+                value: "FAST".to_string(),
+                allowed: vec!["fast".to_string(), "slow".to_string()],
+            })
+        );
+        assert_eq!(
+            config.get_enum_ignore_case("mode", &["fast", "slow"]),
+            Ok("fast".to_string())
+        );
+        assert!(config.get_enum("level", &levels).is_err());
+        assert!(config.get_enum("missing", &levels).is_err());
     }
 
     #[test]
-    #[cfg(feature = "env")]
-    fn test_env_vars() {
-        unsafe {
-            std::env::set_var("KEY13", "overwrite");
-        }
+    #[cfg(feature = "json")]
+    fn test_builder_array_merge() {
+        let build = |policy| {
+            Config::builder()
+                .add_file(File::new_str(
+                    "base",
+                    FileFormat::Json,
+                    "{\"list\": [1, 2], \"other\": 1}",
+                ))
+                .add_file(File::new_str(
+                    "overlay",
+                    FileFormat::Json,
+                    "{\"list\": [2, 3], \"other\": [1]}",
+                ))
+                .array_merge(policy)
+                .build()
+                .unwrap()
+        };
+        let ints = |ints: &[i64]| Value::Array(ints.iter().map(|i| Value::Int(*i)).collect());
+
+        let config = build(ArrayMergePolicy::Replace);
+        assert_eq!(config.get("list").unwrap(), &ints(&[2, 3]));
+        let config = build(ArrayMergePolicy::Concat);
+        assert_eq!(config.get("list").unwrap(), &ints(&[1, 2, 2, 3]));
+        assert_eq!(config.get("other").unwrap(), &ints(&[1]));
+        let config = build(ArrayMergePolicy::Unique);
+        assert_eq!(config.get("list").unwrap(), &ints(&[1, 2, 3]));
+    }
+
+    #[test]
+    #[cfg(feature = "ini")]
+    fn test_builder_ini_repeated_keys() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Ini,
+            "[servers]\nserver=a\nserver=b\nport=80",
+        );
+        let config = Config::builder().add_file(file.clone()).build().unwrap();
+        let servers = config.get("servers").unwrap();
+        assert_eq!(
+            servers.get("server").unwrap(),
+            &Value::String("b".to_string())
+        );
+
+        let config = Config::builder()
+            .add_file(file)
+            .ini_repeated_keys(RepeatedKeyPolicy::Array)
+            .build()
+            .unwrap();
+        let servers = config.get("servers").unwrap();
+        assert_eq!(
+            servers.get("server").unwrap(),
+            &Value::Array(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string())
+            ])
+        );
+        assert_eq!(
+            servers.get("port").unwrap(),
+            &Value::String("80".to_string())
+        );
+    }
 
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_get_in_range() {
         let config = Config::builder()
             .add_file(File::new_str(
                 "test_file",
                 FileFormat::Json,
-                "{\"key13\": \"value\"}",
+                "{\"workers\": 8, \"low\": 0, \"high\": 100, \"ratio\": 1.5}",
             ))
             .build()
             .unwrap();
+        assert_eq!(config.get_i64_in_range("workers", 1, 16), Ok(8));
         assert_eq!(
-            config.get("key13").unwrap(),
-            &Value::String("overwrite".to_string())
+            config.get_i64_in_range("low", 1, 16),
+            Err(ConfigError::OutOfRange {
+                key: "low".to_string(),
+                value: "0".to_string(),
+                min: "1".to_string(),
+                max: "16".to_string(),
+            })
+        );
+        assert!(config.get_i64_in_range("high", 1, 16).is_err());
+        assert_eq!(config.get_i64_clamped("low", 1, 16), Ok(1));
+        assert_eq!(config.get_i64_clamped("high", 1, 16), Ok(16));
+        assert_eq!(config.get_f64_in_range("ratio", 0.0, 2.0), Ok(1.5));
+        assert!(config.get_f64_in_range("ratio", 0.0, 1.0).is_err());
+        assert_eq!(config.get_f64_clamped("ratio", 0.0, 1.0), Ok(1.0));
+        assert_eq!(
+            config.get_f64_clamped("ratio", 1.0, 0.0),
+            Err(ConfigError::InvalidRange {
+                key: "ratio".to_string(),
+                value: "1..=0".to_string(),
+                reason: "min is greater than max".to_string(),
+            })
+        );
+        assert!(matches!(
+            config.get_f64_clamped("ratio", f64::NAN, 1.0),
+            Err(ConfigError::InvalidRange { reason, .. }) if reason == "bounds must not be NaN"
+        ));
+        assert!(config.get_i64_clamped("low", 16, 1).is_err());
+        assert_eq!(
+            config.get_i64_in_range("missing", 1, 16),
+            Err(ConfigError::MissingKey("missing".to_string()))
         );
+    }
 
-        unsafe {
-            std::env::remove_var("KEY13");
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_deserialize_section() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct DbConfig {
+            host: String,
+            port: u16,
+            pool: Option<u32>,
         }
+
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"services": {"database": {"host": "localhost", "port": 5432}}}"#,
+        );
+        let config = Config::builder().add_file(file).build().unwrap();
+        let db: DbConfig = config.deserialize_section("services.database").unwrap();
+        assert_eq!(
+            db,
+            DbConfig {
+                host: "localhost".to_string(),
+                port: 5432,
+                pool: None,
+            }
+        );
+        assert_eq!(
+            config.deserialize_section::<DbConfig>("services.cache"),
+            Err(ConfigError::MissingKey("services.cache".to_string()))
+        );
+        assert!(matches!(
+            config.deserialize_section::<DbConfig>("services"),
+            Err(ConfigError::Deserialize(_))
+        ));
     }
 
     #[test]
-    #[cfg(feature = "env")]
-    fn test_env_vars_table() {
-        unsafe {
-            std::env::set_var("KEY14", "overwrite");
+    #[cfg(feature = "json")]
+    fn test_config_get_as() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Server {
+            host: String,
+            tls: Tls,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Tls {
+            cert: String,
+            ports: Vec<u16>,
         }
 
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"server": {"host": "localhost", "tls": {"cert": "a.pem", "ports": [443, 8443]}}}"#,
+        );
+        let config = Config::builder().add_file(file).build().unwrap();
+        let server: Server = config.get_as("server").unwrap();
+        assert_eq!(
+            server,
+            Server {
+                host: "localhost".to_string(),
+                tls: Tls {
+                    cert: "a.pem".to_string(),
+                    ports: vec![443, 8443],
+                },
+            }
+        );
+        let value: Value = config.get_as("server").unwrap();
+        assert_eq!(&value, config.get("server").unwrap());
+        assert_eq!(
+            config.get_as::<Server>("client"),
+            Err(ConfigError::MissingKey("client".to_string()))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_try_deserialize() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct AppSettings {
+            name: String,
+            database: Database,
+            replicas: Vec<Database>,
+            timeout: Option<u32>,
+            #[serde(default)]
+            verbose: bool,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Database {
+            host: String,
+            port: u16,
+        }
+
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{
+                "name": "app",
+                "database": {"host": "db", "port": 5432},
+                "replicas": [{"host": "r1", "port": 5433}, {"host": "r2", "port": 5434}],
+                "unused": 1
+            }"#,
+        );
+        let mut config = Config::builder().add_file(file).build().unwrap();
+        config.set("name", Value::from("changed"));
+        let settings: AppSettings = config.try_deserialize().unwrap();
+        assert_eq!(
+            settings,
+            AppSettings {
+                name: "changed".to_string(),
+                database: Database {
+                    host: "db".to_string(),
+                    port: 5432,
+                },
+                replicas: vec![
+                    Database {
+                        host: "r1".to_string(),
+                        port: 5433,
+                    },
+                    Database {
+                        host: "r2".to_string(),
+                        port: 5434,
+                    },
+                ],
+                timeout: None,
+                verbose: false,
+            }
+        );
+
+        config.set("database", Value::from("db"));
+        assert!(matches!(
+            config.try_deserialize::<AppSettings>(),
+            Err(ConfigError::Deserialize(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_save_default() {
+        let mut config = Config::builder()
+            .save_format(FileFormat::Json)
+            .build()
+            .unwrap();
+        config.set_value("key", "value");
+        assert_eq!(config.save_default().unwrap(), r#"{"key":"value"}"#);
+
+        let config = Config::builder().build().unwrap();
+        assert_eq!(
+            config.save_default().unwrap_err(),
+            ConfigError::NoSaveFormat
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_builder_yaml_save_style() {
+        let build = |style, marker| {
+            let mut config = Config::builder()
+                .yaml_style(style)
+                .yaml_document_marker(marker)
+                .build()
+                .unwrap();
+            config.set_value("name", "a, b");
+            config.set_value("list", vec![Value::Int(1), Value::Int(2)]);
+            config.save(FileFormat::Yaml).unwrap()
+        };
+        assert_eq!(
+            build(YamlStyle::Block, true),
+            "---\nname: \"a, b\"\nlist:\n  - 1\n  - 2"
+        );
+        assert_eq!(
+            build(YamlStyle::Block, false),
+            "name: \"a, b\"\nlist:\n  - 1\n  - 2"
+        );
+        assert_eq!(
+            build(YamlStyle::Flow, true),
+            "--- {name: \"a, b\", list: [1, 2]}"
+        );
+        let flow = build(YamlStyle::Flow, false);
+        assert_eq!(flow, "{name: \"a, b\", list: [1, 2]}");
+        let loaded = load_map(flow, FileFormat::Yaml).unwrap();
+        assert_eq!(
+            loaded["list"],
+            Value::Array(vec![Value::Int(1), Value::Int(2)])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_builder_toml_inline_tables() {
+        let file = File::new_str("test_file", FileFormat::Toml, "point = { x = 1, y = 2 }");
+        let mut config = Config::builder()
+            .add_file(file.clone())
+            .toml_inline_tables(true)
+            .build()
+            .unwrap();
+        config.set("point", config.get_owned("point").unwrap());
+        assert_eq!(
+            config.save(FileFormat::Toml).unwrap(),
+            "point = { x = 1, y = 2 }\n"
+        );
+
+        let mut config = Config::builder().add_file(file).build().unwrap();
+        config.set("point", config.get_owned("point").unwrap());
+        assert_eq!(
+            config.save(FileFormat::Toml).unwrap(),
+            "[point]\nx = 1\ny = 2\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_list() {
         let config = Config::builder()
             .add_file(File::new_str(
                 "test_file",
                 FileFormat::Json,
-                "{\"key14\": {\"key15\": \"value\"}}",
+                "{\"key3\": \"value\"}",
             ))
             .build()
             .unwrap();
-        let mut expected = Map::new();
-        expected.insert("key15".to_string(), Value::String("value".to_string()));
-        assert_eq!(config.get("key14").unwrap(), &Value::Table(expected));
-
-        unsafe {
-            std::env::remove_var("KEY14");
-        }
+        assert_eq!(config.list(), vec!["key3".to_string()]);
     }
 
-    mod serialize_deserialize {
+    #[cfg(feature = "json")]
+    mod config_display {
         use super::*;
+        use std::fmt::{self, Write};
 
         #[test]
-        #[cfg(feature = "ini")]
-        fn test_deserialize_ini() {
-            let ini = r#"[section]
-key: "value""#;
-            let map = load_map(ini.to_string(), FileFormat::Ini);
-            assert!(map.is_ok());
+        fn test_config_display() {
+            let config = Config::builder()
+                .add_file(File::new_str(
+                    "test_file",
+                    FileFormat::Json,
+                    "{\"key3_2\": \"value\"}",
+                ))
+                .build()
+                .unwrap();
+
+            let mut output = String::new();
+            let result = write!(&mut output, "{}", config);
+
+            assert!(result.is_ok());
+            assert_eq!(output, "key3_2: \"value\"\n");
         }
 
         #[test]
-        #[cfg(feature = "ini")]
-        fn test_serialize_ini() {
-            let map = Map::new();
-            let ini = save_map(&map, FileFormat::Ini);
-            assert!(ini.is_err());
+        fn test_config_display_secret() {
+            let config = Config::builder()
+                .add_file(File::new_str(
+                    "test_file",
+                    FileFormat::Json,
+                    r#"{"db": {"password": "hunter2", "user": "admin"}, "name": "app", "token": "abc"}"#,
+                ))
+                .secret("token")
+                .secret("db.password")
+                .build()
+                .unwrap();
+
+            assert_eq!(
+                config.to_string(),
+                "db: {(password: ***), (user: \"admin\")}\nname: \"app\"\ntoken: ***\n"
+            );
+            assert_eq!(
+                config.to_string_unredacted(),
+                "db: {(password: \"hunter2\"), (user: \"admin\")}\nname: \"app\"\ntoken: \"abc\"\n"
+            );
+        }
+
+        #[test]
+        fn test_config_display_secret_array_and_quoted() {
+            let config = Config::builder()
+                .add_file(File::new_str(
+                    "test_file",
+                    FileFormat::Json,
+                    r#"{"dbs": [{"password": "a", "user": "x"}, {"password": "b"}], "my.key": {"token": "t"}, "name": "app"}"#,
+                ))
+                .secret("dbs.password")
+                .secret("\"my.key\".token")
+                .build()
+                .unwrap();
+
+            assert_eq!(
+                config.to_string(),
+                "dbs: [{(password: ***), (user: \"x\")}, {(password: ***)}]\nmy.key: {(token: ***)}\nname: \"app\"\n"
+            );
+            assert_eq!(
+                config.to_string_unredacted(),
+                "dbs: [{(password: \"a\"), (user: \"x\")}, {(password: \"b\")}]\nmy.key: {(token: \"t\")}\nname: \"app\"\n"
+            );
+        }
+
+        struct FailingWriter;
+
+        impl Write for FailingWriter {
+            fn write_str(&mut self, _s: &str) -> fmt::Result {
+                Err(fmt::Error) // Simulate a write failure
+            }
+        }
+
+        #[test]
+        fn test_config_display_write_error() {
+            let config = Config::builder()
+                .add_file(File::new_str(
+                    "test_file",
+                    FileFormat::Json,
+                    "{\"key3_2\": \"value\"}",
+                ))
+                .build()
+                .unwrap();
+
+            let mut failing_writer = FailingWriter;
+            let result = write!(&mut failing_writer, "{}", config);
+
+            assert!(result.is_err()); // Ensure that write errors propagate
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "load_after_build")]
+    #[cfg(feature = "json")]
+    fn test_config_load() {
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key4\": \"value\"}",
+            ))
+            .build()
+            .unwrap();
+        config
+            .load(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key4\": \"new_value\", \"key5\": \"another_value\"}",
+            ))
+            .unwrap();
+        assert_eq!(
+            config.get("key4").unwrap(),
+            &Value::String("new_value".to_string())
+        );
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key6\": \"value\"}",
+            ))
+            .build()
+            .unwrap()
+            .load(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key6\": \"new_value}",
+            ));
+        assert!(config.is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "load_after_build", feature = "json"))]
+    fn test_config_merge_file() {
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "base.json",
+                FileFormat::Json,
+                r#"{"server": {"host": "a"}, "name": "app"}"#,
+            ))
+            .build()
+            .unwrap();
+        config.set("name", "changed".into());
+        config
+            .merge_file(File::new_str(
+                "extra.json",
+                FileFormat::Json,
+                r#"{"server": {"port": 80}, "name": "extra", "debug": true}"#,
+            ))
+            .unwrap();
+        assert_eq!(config.get_path_owned("server.host"), Some(Value::from("a")));
+        assert_eq!(config.get_path_owned("server.port"), Some(Value::Int(80)));
+        assert_eq!(
+            config.get_with_source("debug"),
+            Some((&Value::Bool(true), "extra.json"))
+        );
+        assert_eq!(config.get("name"), Some(&Value::from("changed")));
+        assert_eq!(
+            config.save(FileFormat::Json).unwrap(),
+            r#"{"name":"changed"}"#
+        );
+
+        let result = config.merge_file(File::new_str("bad.json", FileFormat::Json, "{"));
+        assert!(matches!(result, Err(ConfigError::Parse { path, .. }) if path == "bad.json"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "load_after_build", feature = "env", feature = "json"))]
+    fn test_config_merge_file_keeps_env_and_overrides() {
+        unsafe {
+            std::env::set_var("KEY35", "from_env");
+        }
+
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "base.json",
+                FileFormat::Json,
+                r#"{"key35": "from_base", "key36": "from_base", "server": {"port": 80}}"#,
+            ))
+            .override_value("key36", "from_override")
+            .override_value("server.port", 443)
+            .build()
+            .unwrap();
+        config
+            .merge_file(File::new_str(
+                "extra.json",
+                FileFormat::Json,
+                r#"{"key35": "from_extra", "key36": "from_extra", "server": {"port": 8080, "host": "a"}}"#,
+            ))
+            .unwrap();
+        assert_eq!(
+            config.get_with_source("key35"),
+            Some((&Value::from("from_env"), "env"))
+        );
+        assert_eq!(
+            config.get_with_source("key36"),
+            Some((&Value::from("from_override"), "override"))
+        );
+        assert_eq!(config.get_path("server.port"), Some(&Value::Int(443)));
+        assert_eq!(config.get_path("server.host"), Some(&Value::from("a")));
+        assert_eq!(config.env_overrides(), vec!["key35".to_string()]);
+
+        unsafe {
+            std::env::remove_var("KEY35");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "load_after_build", feature = "json"))]
+    fn test_config_merge_file_builder_options() {
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "base.json",
+                FileFormat::Json,
+                r#"{"tags": ["a"], "port": 80, "name": "app"}"#,
+            ))
+            .array_merge(ArrayMergePolicy::Concat)
+            .json_allow_comments(true)
+            .override_value("port", 443)
+            .build()
+            .unwrap();
+        config.set("name", "changed".into());
+        config
+            .merge_file(File::new_str(
+                "extra.json",
+                FileFormat::Json,
+                "{\n  // more tags\n  \"tags\": [\"b\"], \"port\": 8080, \"name\": \"extra\"\n}",
+            ))
+            .unwrap();
+        assert_eq!(
+            config.get("tags"),
+            Some(&Value::Array(vec![Value::from("a"), Value::from("b")]))
+        );
+        assert_eq!(
+            config.get_with_source("port"),
+            Some((&Value::Int(443), "override"))
+        );
+        assert_eq!(
+            config.get_with_source("name"),
+            Some((&Value::from("changed"), "changes"))
+        );
+
+        config
+            .extend_from_str(FileFormat::Json, r#"{"port": 9090, "tags": ["c"]}"#)
+            .unwrap();
+        assert_eq!(config.get("port"), Some(&Value::Int(443)));
+        assert_eq!(
+            config.get("tags").and_then(Value::as_array).map(Vec::len),
+            Some(3)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_save() {
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key7\": \"value\"}",
+            ))
+            .build()
+            .unwrap();
+        config.set("key7", Value::String("new_value".to_string()));
+        let save = config.save(FileFormat::Json).unwrap();
+        assert_eq!(save, "{\"key7\":\"new_value\"}");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_save_full() {
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"name": "app", "server": {"port": 80}}"#,
+            ))
+            .build()
+            .unwrap();
+        config.set("name", "changed".into());
+        assert_eq!(
+            config.save(FileFormat::Json).unwrap(),
+            r#"{"name":"changed"}"#
+        );
+        assert_eq!(
+            config.save_full(FileFormat::Json).unwrap(),
+            r#"{"name":"changed","server":{"port":80}}"#
+        );
+        #[cfg(feature = "toml")]
+        assert_eq!(
+            config.save_full(FileFormat::Toml).unwrap(),
+            "name = \"changed\"\n\n[server]\nport = 80\n"
+        );
+    }
+
+    #[test]
+    fn test_builder_failed_parse_file() {
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key8\": \"value}",
+            ))
+            .build();
+        assert!(config.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_builder_load() {
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key9\": \"value\"}",
+            ))
+            .load(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key9\": \"new_value\"}",
+            ))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get("key9").unwrap(),
+            &Value::String("new_value".to_string())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_builder_load_failure() {
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key10\": \"value\"}",
+            ))
+            .load(File::new_str("save.json", FileFormat::Json, ""))
+            .unwrap()
+            .build();
+        assert!(matches!(config, Err(ConfigError::Parse { path, .. }) if path == "save.json"));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_builder_load_none() {
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key11\": \"value\"}",
+            ))
+            .load(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key12\": \"new_value\"}",
+            ))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get("key11").unwrap(),
+            &Value::String("value".to_string())
+        );
+        assert!(config.get("key12").is_none());
+        assert_eq!(
+            config.warnings(),
+            &[Warning::IgnoredChange("key12".to_string())]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "load_after_build", feature = "json"))]
+    fn test_config_load_deep_merge() {
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"server": {"host": "x", "port": 8080}, "tags": ["a", "b"]}"#,
+            ))
+            .build()
+            .unwrap();
+        config
+            .load(File::new_str(
+                "save.json",
+                FileFormat::Json,
+                r#"{"server": {"port": 9000}, "tags": ["c"]}"#,
+            ))
+            .unwrap();
+        assert_eq!(config.get_path("server.host"), Some(&Value::from("x")));
+        assert_eq!(config.get_path("server.port"), Some(&Value::Int(9000)));
+        assert_eq!(
+            config.get("tags"),
+            Some(&Value::Array(vec![Value::from("c")]))
+        );
+        assert_eq!(
+            config.save(FileFormat::Json).unwrap(),
+            r#"{"server":{"port":9000},"tags":["c"]}"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_builder_load_deep_merge() {
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"server": {"host": "x", "port": 8080}}"#,
+            ))
+            .load(File::new_str(
+                "save.json",
+                FileFormat::Json,
+                r#"{"server": {"port": 9000}}"#,
+            ))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(config.get_path("server.host"), Some(&Value::from("x")));
+        assert_eq!(config.get_path("server.port"), Some(&Value::Int(9000)));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_builder_allow_new_keys() {
+        let build = |allow: bool| {
+            Config::builder()
+                .add_file(File::new_str(
+                    "test_file",
+                    FileFormat::Json,
+                    r#"{"key": "value"}"#,
+                ))
+                .allow_new_keys(allow)
+                .load(File::new_str(
+                    "save.json",
+                    FileFormat::Json,
+                    r#"{"key": "changed", "new_key": "new_value"}"#,
+                ))
+                .unwrap()
+                .build()
+                .unwrap()
+        };
+        let config = build(true);
+        assert_eq!(config.get("key"), Some(&Value::from("changed")));
+        assert_eq!(
+            config.get_with_source("new_key"),
+            Some((&Value::from("new_value"), "changes"))
+        );
+        assert!(config.warnings().is_empty());
+
+        let config = build(false);
+        assert_eq!(config.get("key"), Some(&Value::from("changed")));
+        assert_eq!(config.get("new_key"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_builder_allow_new_keys_nested() {
+        let build = |allow: bool| {
+            Config::builder()
+                .add_file(File::new_str(
+                    "test_file",
+                    FileFormat::Json,
+                    r#"{"server": {"port": 80, "tls": {"cert": "a"}}}"#,
+                ))
+                .allow_new_keys(allow)
+                .load(File::new_str(
+                    "save.json",
+                    FileFormat::Json,
+                    r#"{"server": {"port": 9000, "debug": true, "tls": {"key": "b"}}}"#,
+                ))
+                .unwrap()
+                .build()
+                .unwrap()
+        };
+        let config = build(false);
+        assert_eq!(config.get_path("server.port"), Some(&Value::Int(9000)));
+        assert_eq!(config.get_path("server.debug"), None);
+        assert_eq!(config.get_path("server.tls.key"), None);
+        assert_eq!(
+            config.warnings(),
+            &[
+                Warning::IgnoredChange("server.debug".to_string()),
+                Warning::IgnoredChange("server.tls.key".to_string()),
+            ]
+        );
+
+        let config = build(true);
+        assert_eq!(config.get_path("server.debug"), Some(&Value::Bool(true)));
+        assert_eq!(config.get_path("server.tls.key"), Some(&Value::from("b")));
+        assert!(config.warnings().is_empty());
+    }
+
+    #[test]
+    #[cfg(all(feature = "load_after_build", feature = "json"))]
+    fn test_config_load_allow_new_keys() {
+        let save = File::new_str(
+            "save.json",
+            FileFormat::Json,
+            r#"{"key": "changed", "new_key": "new_value"}"#,
+        );
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"key": "value"}"#,
+            ))
+            .allow_new_keys(true)
+            .build()
+            .unwrap();
+        config.load(save.clone()).unwrap();
+        assert_eq!(config.get("key"), Some(&Value::from("changed")));
+        assert_eq!(config.get("new_key"), Some(&Value::from("new_value")));
+
+        let mut config = Config::builder().build().unwrap();
+        config.load(save).unwrap();
+        assert_eq!(config.get("new_key"), None);
+
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"server": {"port": 80}}"#,
+            ))
+            .build()
+            .unwrap();
+        config
+            .load(File::new_str(
+                "save.json",
+                FileFormat::Json,
+                r#"{"server": {"port": 9000, "debug": true}}"#,
+            ))
+            .unwrap();
+        assert_eq!(config.get_path("server.port"), Some(&Value::Int(9000)));
+        assert_eq!(config.get_path("server.debug"), None);
+        assert_eq!(
+            config.warnings(),
+            &[Warning::IgnoredChange("server.debug".to_string())]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "load_after_build", feature = "env", feature = "json"))]
+    fn test_config_load_keeps_layers() {
+        unsafe {
+            std::env::set_var("KEY32", "from_env");
+        }
+
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"key32": "from_file", "key33": "from_file", "key34": "from_file", "title": "app", "count": "5"}"#,
+            ))
+            .override_value("key33", "from_override")
+            .override_value("title.first", "x")
+            .build()
+            .unwrap();
+        config.infer_types();
+        config
+            .load(File::new_str(
+                "save.json",
+                FileFormat::Json,
+                r#"{"key34": "from_change"}"#,
+            ))
+            .unwrap();
+        assert_eq!(
+            config.get_with_source("key32"),
+            Some((&Value::from("from_env"), "env"))
+        );
+        assert_eq!(
+            config.get_with_source("key33"),
+            Some((&Value::from("from_override"), "override"))
+        );
+        assert_eq!(
+            config.get_with_source("key34"),
+            Some((&Value::from("from_change"), "changes"))
+        );
+        assert_eq!(config.get_path("title.first"), Some(&Value::from("x")));
+        assert_eq!(config.get("count"), Some(&Value::Int(5)));
+
+        unsafe {
+            std::env::remove_var("KEY32");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "load_after_build", feature = "intern", feature = "json"))]
+    fn test_config_load_interned() {
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"a": "debug", "b": "info"}"#,
+            ))
+            .build()
+            .unwrap();
+        config.intern_strings();
+        config
+            .load(File::new_str(
+                "save.json",
+                FileFormat::Json,
+                r#"{"b": "debug"}"#,
+            ))
+            .unwrap();
+        match (config.get("a"), config.get("b")) {
+            (Some(Value::Shared(a)), Some(Value::Shared(b))) => assert!(Arc::ptr_eq(a, b)),
+            other => panic!("expected shared strings, found {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_from_serializable() {
+        #[derive(serde::Serialize)]
+        struct Settings {
+            name: String,
+            port: u16,
+            database: Database,
+        }
+
+        #[derive(serde::Serialize)]
+        struct Database {
+            host: String,
+        }
+
+        let settings = Settings {
+            name: "app".to_string(),
+            port: 8080,
+            database: Database {
+                host: "localhost".to_string(),
+            },
+        };
+        let config = Config::from_serializable(&settings).unwrap();
+        assert_eq!(config.get("port").unwrap(), &Value::Int(8080));
+        assert_eq!(
+            config.save(FileFormat::Json).unwrap(),
+            r#"{"database":{"host":"localhost"},"name":"app","port":8080}"#
+        );
+    }
+
+    #[test]
+    fn test_config_from_serializable_not_table() {
+        assert_eq!(
+            Config::from_serializable(&1).err().unwrap(),
+            ConfigError::Serialize("config root must be a table, found Int".to_string())
+        );
+        assert!(Config::from_serializable(&vec![1, 2]).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "read_file", feature = "json"))]
+    fn test_builder_add_first_existing() {
+        let dir = std::env::temp_dir();
+        let missing = dir.join("ronf_first_existing_missing.json");
+        let present = dir.join("ronf_first_existing_present.json");
+        let other = dir.join("ronf_first_existing_other.json");
+        std::fs::write(&present, r#"{"key": "present"}"#).unwrap();
+        std::fs::write(&other, r#"{"key": "other"}"#).unwrap();
+        let paths = [
+            missing.to_str().unwrap(),
+            present.to_str().unwrap(),
+            other.to_str().unwrap(),
+        ];
+
+        let config = Config::builder()
+            .add_first_existing(&paths)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get("key").unwrap(),
+            &Value::String("present".to_string())
+        );
+
+        let builder = Config::builder()
+            .add_first_existing(&[missing.to_str().unwrap()])
+            .unwrap();
+        assert!(builder.files.is_empty());
+
+        std::fs::remove_file(present).unwrap();
+        std::fs::remove_file(other).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(feature = "read_file", feature = "json"))]
+    fn test_builder_add_dir_nested() {
+        let dir = std::env::temp_dir().join(format!("ronf_add_dir_nested_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("redis.json"), r#"{"host": "cache", "port": 6379}"#).unwrap();
+        std::fs::write(dir.join("postgres.json"), r#"{"host": "db"}"#).unwrap();
+        std::fs::write(dir.join("README.txt"), "not a config").unwrap();
+
+        let builder = Config::builder()
+            .add_dir_nested(&dir)
+            .unwrap()
+            .add_file(File {
+                path: "root.json".to_string(),
+                format: FileFormat::Json,
+                content: r#"{"port": 80}"#.to_string(),
+            });
+        assert_eq!(builder.files.len(), 3);
+        let config = builder.build().unwrap();
+        assert_eq!(config.get("port"), Some(&Value::Int(80)));
+        assert_eq!(
+            config.get_path_owned("redis.host"),
+            Some(Value::from("cache"))
+        );
+        assert_eq!(config.get_path_owned("redis.port"), Some(Value::Int(6379)));
+        assert_eq!(
+            config.get_path_owned("postgres.host"),
+            Some(Value::from("db"))
+        );
+        assert_eq!(config.get("host"), None);
+        assert!(
+            Config::builder()
+                .add_dir_nested(dir.join("missing"))
+                .is_err()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_infer_types() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"port": "8080", "debug": "true", "name": "42abc", "nested": {"ratio": "0.5"}}"#,
+        );
+        let mut config = Config::builder().add_file(file).build().unwrap();
+        config.infer_types();
+        assert_eq!(config.get("port").unwrap(), &Value::Int(8080));
+        assert_eq!(config.get("debug").unwrap(), &Value::Bool(true));
+        assert_eq!(config.get("name").unwrap(), &Value::from("42abc"));
+        assert_eq!(
+            config.get_path_owned("nested.ratio"),
+            Some(Value::Float(0.5))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_preserve_string_numbers() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"version": "1.10", "port": "8080", "app": {"zip": "01234", "ratio": "0.5"}}"#,
+        );
+        let mut config = Config::builder()
+            .add_file(file)
+            .preserve_string_numbers(&["version", "app.zip"])
+            .build()
+            .unwrap();
+        config.infer_types();
+        assert_eq!(config.get("version").unwrap(), &Value::from("1.10"));
+        assert_eq!(config.get("port").unwrap(), &Value::Int(8080));
+        assert_eq!(config.get_path_owned("app.zip"), Some(Value::from("01234")));
+        assert_eq!(config.get_path_owned("app.ratio"), Some(Value::Float(0.5)));
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml", feature = "toml"))]
+    fn test_preserve_string_numbers_raw_text() {
+        let yaml = File::new_str(
+            "test_file.yaml",
+            FileFormat::Yaml,
+            "version: 1.10\nzip: 01234\nport: 8080\n",
+        );
+        let toml = File::new_str(
+            "test_file.toml",
+            FileFormat::Toml,
+            "port = 8080\n[app]\nversion = 1.10\n",
+        );
+        let config = Config::builder()
+            .add_file(yaml)
+            .add_file(toml)
+            .preserve_string_numbers(&["version", "zip", "app.version"])
+            .build()
+            .unwrap();
+        assert_eq!(config.get("version").unwrap(), &Value::from("1.10"));
+        assert_eq!(config.get("zip").unwrap(), &Value::from("01234"));
+        assert_eq!(config.get("port").unwrap(), &Value::Int(8080));
+        assert_eq!(
+            config.get_path_owned("app.version"),
+            Some(Value::from("1.10"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_builder_override_value() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"port": 80, "server": {"host": "a"}, "name": "app", "title": "app"}"#,
+        );
+        let mut builder = Config::builder()
+            .add_file(file)
+            .override_value("port", 443)
+            .override_value("server.tls.enabled", true)
+            .override_value("name.first", "x")
+            .override_value("title.first", "x");
+        builder
+            .changes
+            .insert("name".to_string(), Value::from("changed"));
+        let config = builder.build().unwrap();
+        assert_eq!(config.get("port").unwrap(), &Value::Int(443));
+        assert_eq!(config.get_path_owned("server.host"), Some(Value::from("a")));
+        assert_eq!(
+            config.get_path_owned("server.tls.enabled"),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(config.get("name"), Some(&Value::from("changed")));
+        assert_eq!(config.get_path("title.first"), Some(&Value::from("x")));
+        assert_eq!(
+            config.save(FileFormat::Json).unwrap(),
+            r#"{"name":"changed"}"#
+        );
+    }
+
+    #[test]
+    fn test_config_entries_sorted() {
+        let mut config = Config::builder().build().unwrap();
+        config.set_value("zeta", 1);
+        config.set_value(
+            "alpha",
+            Map::from_iter([
+                ("y".to_string(), Value::Int(2)),
+                (
+                    "b".to_string(),
+                    Value::Table(Map::from_iter([("c".to_string(), Value::Int(3))])),
+                ),
+            ]),
+        );
+        config.set_value("mid", Vec::<Value>::new());
+        config.set_value("empty", Map::new());
+
+        assert_eq!(config.list(), vec!["zeta", "alpha", "mid", "empty"]);
+        let keys: Vec<String> = config.entries_sorted().into_iter().map(|e| e.0).collect();
+        assert_eq!(keys, vec!["alpha", "empty", "mid", "zeta"]);
+        assert_eq!(
+            config.entries_flat_sorted(),
+            vec![
+                ("alpha.b.c".to_string(), &Value::Int(3)),
+                ("alpha.y".to_string(), &Value::Int(2)),
+                ("empty".to_string(), &Value::Table(Table::new())),
+                ("mid".to_string(), &Value::Array(vec![])),
+                ("zeta".to_string(), &Value::Int(1)),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_to_json_value() {
+        let content = r#"{"name": "app", "port": 80, "ratio": 0.5, "db": {"hosts": ["a", "b"], "tls": null}}"#;
+        let file = File::new_str("test_file", FileFormat::Json, content);
+        let mut config = Config::builder().add_file(file).build().unwrap();
+        config.set_value("port", 8080);
+        let mut expected: serde_json::Value = serde_json::from_str(content).unwrap();
+        expected["port"] = serde_json::json!(8080);
+        assert_eq!(config.to_json_value(), expected);
+    }
+
+    #[test]
+    #[cfg(feature = "jsonschema")]
+    fn test_config_validate_schema() {
+        let schema = r#"{
+            "type": "object",
+            "properties": {"port": {"type": "integer"}},
+            "required": ["port", "name"]
+        }"#;
+        let file = File::new_str("test_file", FileFormat::Json, r#"{"port": "80"}"#);
+        let mut config = Config::builder().add_file(file).build().unwrap();
+        let errors = config.validate_schema(schema).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.starts_with("/port: ")));
+        assert!(
+            errors
+                .iter()
+                .any(|e| e.contains("\"name\" is a required property"))
+        );
+
+        config.set_value("port", 80);
+        config.set_value("name", "app");
+        assert!(config.validate_schema(schema).is_ok());
+        assert!(config.validate_schema("{").unwrap_err()[0].starts_with("Invalid schema"));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_map_values() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"name": " app ", "port": 80, "db": {"host": " localhost", "tags": [" a ", 1]}}"#,
+        );
+        let mut config = Config::builder().add_file(file).build().unwrap();
+        config.map_values(|value| match value {
+            Value::String(s) => Value::String(s.trim().to_string()),
+            other => other.clone(),
+        });
+        assert_eq!(config.get("name").unwrap(), &Value::from("app"));
+        assert_eq!(config.get("port").unwrap(), &Value::Int(80));
+        assert_eq!(
+            config.get_path_owned("db.host"),
+            Some(Value::from("localhost"))
+        );
+        assert_eq!(
+            config.get_path_owned("db.tags"),
+            Some(Value::Array(vec![Value::from("a"), Value::Int(1)]))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_remove_path() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"server": {"tls": {"cert": "a.pem"}, "port": 80}, "name": "app"}"#,
+        );
+        let mut config = Config::builder().add_file(file).build().unwrap();
+        config.set_value("name", "changed");
+
+        assert_eq!(
+            config.remove_path("server.tls.cert"),
+            Some(Value::String("a.pem".to_string()))
+        );
+        assert_eq!(config.get_table_path("server.tls"), Some(&Table::new()));
+        assert_eq!(config.remove_path("server.tls.cert"), None);
+        assert_eq!(config.remove_path("server.port.value"), None);
+        assert_eq!(config.remove_path("missing"), None);
+
+        assert_eq!(
+            config.remove_path("name"),
+            Some(Value::String("changed".to_string()))
+        );
+        assert_eq!(config.get("name"), None);
+        assert_eq!(config.save(FileFormat::Json).unwrap(), "{}");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_remove_path_prune() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"a": {"b": {"c": 1}}, "x": {"y": 1, "z": 2}}"#,
+        );
+        let mut config = Config::builder().add_file(file).build().unwrap();
+        assert_eq!(config.remove_path_prune("a.b.c"), Some(Value::Int(1)));
+        assert_eq!(config.get("a"), None);
+        assert_eq!(config.remove_path_prune("x.y"), Some(Value::Int(1)));
+        assert_eq!(config.get_path_owned("x.z"), Some(Value::Int(2)));
+    }
+
+    #[test]
+    #[cfg(all(feature = "load_after_build", feature = "json"))]
+    fn test_config_remove_path_then_merge() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"s": {"x": 1, "y": 2}, "t": {"z": 3}}"#,
+        );
+        let mut config = Config::builder()
+            .add_file(file)
+            .override_value("t.z", 4)
+            .build()
+            .unwrap();
+        assert_eq!(config.remove_path("s.x"), Some(Value::Int(1)));
+        assert_eq!(
+            config.remove_path("t"),
+            Some(Value::from(Map::from_iter([(
+                "z".to_string(),
+                Value::Int(4)
+            )])))
+        );
+        config
+            .merge_file(File::new_str(
+                "other.json",
+                FileFormat::Json,
+                r#"{"other": 3}"#,
+            ))
+            .unwrap();
+        assert_eq!(config.get_path("s.x"), None);
+        assert_eq!(config.get_path("s.y"), Some(&Value::Int(2)));
+        assert_eq!(config.get("t"), None);
+        assert_eq!(config.get("other"), Some(&Value::Int(3)));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_get_bool() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"yes": "yes", "on": "on", "zero": "0", "false": "false",
+                "one": 1, "none": 0.0, "list": []}"#,
+        );
+        let config = Config::builder().add_file(file).build().unwrap();
+        assert_eq!(config.get_bool("yes"), Ok(true));
+        assert_eq!(config.get_bool("on"), Ok(true));
+        assert_eq!(config.get_bool("zero"), Ok(false));
+        assert_eq!(config.get_bool("false"), Ok(false));
+        assert_eq!(config.get_bool("one"), Ok(true));
+        assert_eq!(config.get_bool("none"), Ok(false));
+        assert_eq!(
+            config.get_bool("list"),
+            Err(ConfigError::Convert {
+                key: "list".to_string(),
+                error: CannotConvert::new("Array", "Bool"),
+            })
+        );
+        assert_eq!(
+            config.get_bool("missing"),
+            Err(ConfigError::MissingKey("missing".to_string()))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_contains_key() {
+        let file = File::new_str("test_file", FileFormat::Json, r#"{"key": {"nested": 1}}"#);
+        let config = Config::builder().add_file(file).build().unwrap();
+        assert!(config.contains_key("key"));
+        assert!(!config.contains_key("nested"));
+        assert!(!config.contains_key("missing"));
+        assert!(!config.is_empty());
+
+        let config = Config::builder().build().unwrap();
+        assert!(config.is_empty());
+        assert!(!config.contains_key("key"));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_get_path() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"database": {"connection": {"port": 5432}, "name": "db"}, "a.b": 1}"#,
+        );
+        let config = Config::builder().add_file(file).build().unwrap();
+        assert_eq!(
+            config.get_path("database.connection.port"),
+            Some(&Value::Int(5432))
+        );
+        assert_eq!(config.get_path("database.name"), Some(&Value::from("db")));
+        assert!(config.get_path("database").unwrap().is_table());
+        assert_eq!(config.get_path("\"a.b\""), Some(&Value::Int(1)));
+
+        assert_eq!(config.get_path("database.missing.port"), None);
+        assert_eq!(config.get_path("missing"), None);
+        assert_eq!(config.get_path("database.name.first"), None);
+        assert_eq!(config.get_path("database.connection.port.x"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_set_path() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"server": {"host": "a", "port": 80}, "name": "app"}"#,
+        );
+        let mut config = Config::builder().add_file(file).build().unwrap();
+        config.set_path("server.port", Value::Int(8080)).unwrap();
+        config
+            .set_path("server.tls.cert", Value::from("cert.pem"))
+            .unwrap();
+        config
+            .set_path("new.nested.key", Value::Bool(true))
+            .unwrap();
+        assert_eq!(config.get_path("server.port"), Some(&Value::Int(8080)));
+        assert_eq!(config.get_path("server.host"), Some(&Value::from("a")));
+        assert_eq!(
+            config.get_path("server.tls.cert"),
+            Some(&Value::from("cert.pem"))
+        );
+        assert_eq!(config.get_path("new.nested.key"), Some(&Value::Bool(true)));
+        assert_eq!(
+            config.get_with_source("server").map(|(_, source)| source),
+            Some("set")
+        );
+        assert_eq!(
+            config.save(FileFormat::Json).unwrap(),
+            r#"{"new":{"nested":{"key":true}},"server":{"port":8080,"tls":{"cert":"cert.pem"}}}"#
+        );
+        assert_eq!(
+            config.save_full(FileFormat::Json).unwrap(),
+            r#"{"name":"app","new":{"nested":{"key":true}},"server":{"host":"a","port":8080,"tls":{"cert":"cert.pem"}}}"#
+        );
+
+        assert_eq!(
+            config.set_path("name.first", Value::from("x")),
+            Err(ConfigError::Convert {
+                key: "name".to_string(),
+                error: CannotConvert::new("String", "Table"),
+            })
+        );
+        assert_eq!(
+            config
+                .set_path("server.port.x", Value::Int(1))
+                .unwrap_err()
+                .to_string(),
+            "Key server.port: Cannot convert Int to Table"
+        );
+        assert_eq!(config.get("name"), Some(&Value::from("app")));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_typed_getters() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"name": "app", "port": 8080, "ratio": 0.5, "text": "12", "list": [], "word": "a"}"#,
+        );
+        let config = Config::builder().add_file(file).build().unwrap();
+        assert_eq!(config.get_string("name"), Ok("app".to_string()));
+        assert_eq!(config.get_string("port"), Ok("8080".to_string()));
+        assert_eq!(config.get_int("port"), Ok(8080));
+        assert_eq!(config.get_int("text"), Ok(12));
+        assert_eq!(config.get_float("ratio"), Ok(0.5));
+        assert_eq!(config.get_float("port"), Ok(8080.0));
+
+        assert_eq!(
+            config.get_string("list"),
+            Err(ConfigError::Convert {
+                key: "list".to_string(),
+                error: CannotConvert::new("Array", "String"),
+            })
+        );
+        assert_eq!(
+            config.get_int("word").unwrap_err().to_string(),
+            "Key word: Cannot convert String to Int"
+        );
+        assert_eq!(
+            config.get_float("missing"),
+            Err(ConfigError::MissingKey("missing".to_string()))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_get_socket_addr() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"listen": "127.0.0.1:8080", "host": "::1", "port": 80,
+                "bad": "127.0.0.1", "name": "localhost", "big": 70000, "num": 1}"#,
+        );
+        let config = Config::builder().add_file(file).build().unwrap();
+        assert_eq!(
+            config.get_socket_addr("listen"),
+            Ok(SocketAddr::from(([127, 0, 0, 1], 8080)))
+        );
+        assert_eq!(
+            config.get_socket_addr_parts("host", "port"),
+            Ok("[::1]:80".parse().unwrap())
+        );
+
+        let error = config.get_socket_addr("bad").unwrap_err();
+        assert!(matches!(error, ConfigError::InvalidAddress { ref key, .. } if key == "bad"));
+        assert_eq!(
+            error.to_string(),
+            "Key bad: 127.0.0.1 is not a valid address: invalid socket address syntax"
+        );
+        assert!(matches!(
+            config.get_socket_addr_parts("name", "port"),
+            Err(ConfigError::InvalidAddress { .. })
+        ));
+        assert!(matches!(
+            config.get_socket_addr_parts("host", "big"),
+            Err(ConfigError::OutOfRange { .. })
+        ));
+        assert_eq!(
+            config.get_socket_addr("num"),
+            Err(ConfigError::Convert {
+                key: "num".to_string(),
+                error: CannotConvert::new("Int", "SocketAddr"),
+            })
+        );
+        assert_eq!(
+            config.get_socket_addr("missing"),
+            Err(ConfigError::MissingKey("missing".to_string()))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_config_get_range() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"exclusive": "1..10", "inclusive": " 1..=10 ", "array": [1, 10],
+                "inverted": "10..1", "word": "a..b", "short": [1], "num": 5,
+                "max": "0..=9223372036854775807"}"#,
+        );
+        let config = Config::builder().add_file(file).build().unwrap();
+        assert_eq!(config.get_range("exclusive"), Ok(1..10));
+        assert_eq!(config.get_range("inclusive"), Ok(1..11));
+        assert_eq!(config.get_range("array"), Ok(1..10));
+
+        assert_eq!(
+            config.get_range("inverted").unwrap_err().to_string(),
+            "Key inverted: \"10..1\" is not a valid range, start is greater than end"
+        );
+        assert_eq!(
+            config.get_range("word").unwrap_err().to_string(),
+            "Key word: \"a..b\" is not a valid range, expected `start..end`, `start..=end` or `[start, end]`"
+        );
+        for key in ["short", "num", "max"] {
+            assert!(matches!(
+                config.get_range(key),
+                Err(ConfigError::InvalidRange { .. })
+            ));
+        }
+        assert_eq!(
+            config.get_range("missing"),
+            Err(ConfigError::MissingKey("missing".to_string()))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_builder_detect_conflicts() {
+        let files = [
+            File::new_str("a.json", FileFormat::Json, r#"{"port": 80, "host": "a"}"#),
+            File::new_str("b.json", FileFormat::Json, r#"{"port": 8080, "host": "a"}"#),
+        ];
+        let config = Config::builder()
+            .add_file(files[0].clone())
+            .add_file(files[1].clone())
+            .detect_conflicts(true)
+            .build()
+            .unwrap();
+        assert_eq!(config.get("port").unwrap(), &Value::Int(8080));
+        assert_eq!(
+            config.warnings(),
+            &[Warning::Conflict {
+                key: "port".to_string(),
+                first: "a.json".to_string(),
+                second: "b.json".to_string(),
+            }]
+        );
+
+        let result = Config::builder()
+            .add_file(files[0].clone())
+            .add_file(files[1].clone())
+            .conflicts(ConflictPolicy::Error)
+            .build();
+        assert_eq!(
+            result.err().unwrap(),
+            ConfigError::Conflicts(vec![
+                "Key port is set to different values in a.json and b.json".to_string()
+            ])
+        );
+
+        let config = Config::builder()
+            .add_file(files[0].clone())
+            .add_file(files[1].clone())
+            .build()
+            .unwrap();
+        assert!(config.warnings().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_builder_json_allow_comments() {
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            "{\n  // comment\n  \"key\": \"a // b\" /* block */\n}",
+        );
+        let config = Config::builder()
+            .add_file(file.clone())
+            .json_allow_comments(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get("key").unwrap(),
+            &Value::String("a // b".to_string())
+        );
+        assert!(Config::builder().add_file(file).build().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_load_json_allow_comments() {
+        let defaults = File::new_str("test_file", FileFormat::Json, r#"{"a": 1}"#);
+        let save = File::new_str("save.json", FileFormat::Json, "{\"a\": 2 // c\n}");
+        let config = Config::builder()
+            .add_file(defaults.clone())
+            .load(save.clone())
+            .unwrap()
+            .json_allow_comments(true)
+            .build()
+            .unwrap();
+        assert_eq!(config.get("a"), Some(&Value::Int(2)));
+
+        #[cfg(feature = "load_after_build")]
+        {
+            let mut config = Config::builder()
+                .add_file(defaults)
+                .json_allow_comments(true)
+                .build()
+                .unwrap();
+            config.load(save).unwrap();
+            assert_eq!(config.get("a"), Some(&Value::Int(2)));
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_env_ignore() {
+        unsafe {
+            std::env::set_var("SIGNINGKEY", "from_env");
+            std::env::set_var("KEY14", "from_env");
+        }
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"signingkey\": \"from_file\", \"key14\": \"from_file\"}",
+            ))
+            .env_ignore(&["signingkey"])
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get("signingkey").unwrap(),
+            &Value::String("from_file".to_string())
+        );
+        assert_eq!(
+            config.get("key14").unwrap(),
+            &Value::String("from_env".to_string())
+        );
+
+        unsafe {
+            std::env::remove_var("SIGNINGKEY");
+            std::env::remove_var("KEY14");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_env_precedence() {
+        unsafe {
+            std::env::set_var("KEY15", "from_env");
+        }
+
+        let mut builder = Config::builder().add_file(File::new_str(
+            "test_file",
+            FileFormat::Json,
+            "{\"key15\": \"from_file\", \"key16\": \"from_file\"}",
+        ));
+        builder.changes.insert(
+            "key15".to_string(),
+            Value::String("from_change".to_string()),
+        );
+        builder.changes.insert(
+            "key16".to_string(),
+            Value::String("from_change".to_string()),
+        );
+        let config = builder.build().unwrap();
+        assert_eq!(
+            config.get("key15").unwrap(),
+            &Value::String("from_change".to_string())
+        );
+        assert_eq!(
+            config.get("key16").unwrap(),
+            &Value::String("from_change".to_string())
+        );
+
+        unsafe {
+            std::env::remove_var("KEY15");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_build_layer_order() {
+        unsafe {
+            std::env::set_var("KEY28", "from_env");
+            std::env::set_var("KEY29", "from_env");
+            std::env::set_var("KEY30", "from_env");
+        }
+
+        let save = File::new_str("save.json", FileFormat::Json, r#"{"key28": "from_change"}"#);
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "a.json",
+                FileFormat::Json,
+                r#"{"key28": "from_a", "key29": "from_a", "key30": "from_a", "key31": "from_a"}"#,
+            ))
+            .add_file(File::new_str(
+                "b.json",
+                FileFormat::Json,
+                r#"{"key28": "from_b", "key29": "from_b", "key30": "from_b", "key31": "from_b"}"#,
+            ))
+            .override_value("key28", "from_override")
+            .override_value("key29", "from_override")
+            .load(save)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get_with_source("key28"),
+            Some((&Value::from("from_change"), "changes"))
+        );
+        assert_eq!(
+            config.get_with_source("key29"),
+            Some((&Value::from("from_override"), "override"))
+        );
+        assert_eq!(
+            config.get_with_source("key30"),
+            Some((&Value::from("from_env"), "env"))
+        );
+        assert_eq!(
+            config.get_with_source("key31"),
+            Some((&Value::from("from_b"), "b.json"))
+        );
+
+        unsafe {
+            std::env::remove_var("KEY28");
+            std::env::remove_var("KEY29");
+            std::env::remove_var("KEY30");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "env")]
+    fn test_env_vars() {
+        unsafe {
+            std::env::set_var("KEY13", "overwrite");
+        }
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key13\": \"value\"}",
+            ))
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get("key13").unwrap(),
+            &Value::String("overwrite".to_string())
+        );
+
+        unsafe {
+            std::env::remove_var("KEY13");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "env")]
+    fn test_env_vars_table() {
+        unsafe {
+            std::env::set_var("KEY14", "overwrite");
+        }
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key14\": {\"key15\": \"value\"}}",
+            ))
+            .build()
+            .unwrap();
+        let mut expected = Map::new();
+        expected.insert("key15".to_string(), Value::String("value".to_string()));
+        assert_eq!(config.get("key14").unwrap(), &Value::Table(expected));
+
+        unsafe {
+            std::env::remove_var("KEY14");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_env_vars_nested() {
+        unsafe {
+            std::env::set_var("KEY20__PORT", "from_env");
+            std::env::set_var("KEY21__DB__PORT", "from_env");
+            std::env::set_var("KEY21__DB__MISSING", "from_env");
+            std::env::set_var("KEY21__DB", "from_env");
+        }
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"key20": {"port": "value"}, "key21": {"db": {"port": "value", "host": "value"}}}"#,
+            ))
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get_path("key20.port"),
+            Some(&Value::from("from_env"))
+        );
+        assert_eq!(
+            config.get_path("key21.db.port"),
+            Some(&Value::from("from_env"))
+        );
+        assert_eq!(
+            config.get_path("key21.db.host"),
+            Some(&Value::from("value"))
+        );
+        assert_eq!(config.get_path("key21.db.missing"), None);
+        assert_eq!(config.get_with_source("key21").unwrap().1, "env");
+        let overrides = config.env_overrides();
+        assert!(overrides.contains(&"key20.port".to_string()));
+        assert!(overrides.contains(&"key21.db.port".to_string()));
+        assert!(!overrides.contains(&"key21.db".to_string()));
+
+        unsafe {
+            std::env::remove_var("KEY20__PORT");
+            std::env::remove_var("KEY21__DB__PORT");
+            std::env::remove_var("KEY21__DB__MISSING");
+            std::env::remove_var("KEY21__DB");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_env_prefix() {
+        unsafe {
+            std::env::set_var("MYAPP_KEY22", "from_env");
+            std::env::set_var("MYAPP_KEY24__PORT", "from_env");
+            std::env::set_var("KEY23", "from_env");
+        }
+
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"key22": "value", "key23": "value", "key24": {"port": "value"}}"#,
+        );
+        let config = Config::builder()
+            .add_file(file.clone())
+            .env_prefix("myapp_")
+            .build()
+            .unwrap();
+        assert_eq!(config.get("key22"), Some(&Value::from("from_env")));
+        assert_eq!(config.get("key23"), Some(&Value::from("value")));
+        assert_eq!(
+            config.get_path("key24.port"),
+            Some(&Value::from("from_env"))
+        );
+
+        let config = Config::builder().add_file(file).build().unwrap();
+        assert_eq!(config.get("key22"), Some(&Value::from("value")));
+        assert_eq!(config.get("key23"), Some(&Value::from("from_env")));
+
+        unsafe {
+            std::env::remove_var("MYAPP_KEY22");
+            std::env::remove_var("MYAPP_KEY24__PORT");
+            std::env::remove_var("KEY23");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_env_prefix_new_keys() {
+        unsafe {
+            std::env::set_var("NEWKEYS_DB__HOST", "from_env");
+            std::env::set_var("NEWKEYS_DB__PORT", "5432");
+            std::env::set_var("NEWKEYS_NAME__FIRST", "from_env");
+        }
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"db": {"port": 80}, "name": "app"}"#,
+            ))
+            .env_prefix("newkeys_")
+            .build()
+            .unwrap();
+        assert_eq!(config.get_path("db.host"), Some(&Value::from("from_env")));
+        assert_eq!(config.get_path("db.port"), Some(&Value::Int(5432)));
+        assert_eq!(config.get("name"), Some(&Value::from("app")));
+        let mut overrides = config.env_overrides();
+        overrides.sort();
+        assert_eq!(
+            overrides,
+            vec!["db.host".to_string(), "db.port".to_string()]
+        );
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"db": {"port": 80}}"#,
+            ))
+            .env_prefix("NEWKEYS")
+            .build()
+            .unwrap();
+        let mut overrides = config.env_overrides();
+        overrides.sort();
+        assert_eq!(
+            overrides,
+            vec![
+                "db.host".to_string(),
+                "db.port".to_string(),
+                "name.first".to_string()
+            ]
+        );
+
+        unsafe {
+            std::env::remove_var("NEWKEYS_DB__HOST");
+            std::env::remove_var("NEWKEYS_DB__PORT");
+            std::env::remove_var("NEWKEYS_NAME__FIRST");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_env_ignore_path() {
+        unsafe {
+            std::env::set_var("IGNOREPATH_DB__PASSWORD", "from_env");
+            std::env::set_var("IGNOREPATH_DB__USER", "from_env");
+            std::env::set_var("IGNOREPATH_TLS__CERT__PATH", "from_env");
+        }
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"db": {"password": "x", "user": "x"}, "tls": {"cert": {"path": "x"}}}"#,
+            ))
+            .env_prefix("ignorepath_")
+            .env_ignore(&["db.password", "tls.cert"])
+            .build()
+            .unwrap();
+        assert_eq!(config.get_path("db.password"), Some(&Value::from("x")));
+        assert_eq!(config.get_path("db.user"), Some(&Value::from("from_env")));
+        assert_eq!(config.get_path("tls.cert.path"), Some(&Value::from("x")));
+
+        unsafe {
+            std::env::remove_var("IGNOREPATH_DB__PASSWORD");
+            std::env::remove_var("IGNOREPATH_DB__USER");
+            std::env::remove_var("IGNOREPATH_TLS__CERT__PATH");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_env_separator() {
+        unsafe {
+            std::env::set_var("KEY25__FULL_SCREEN", "from_env");
+            std::env::set_var("KEY26_FULL_SCREEN", "from_env");
+        }
+
+        let file = File::new_str(
+            "test_file",
+            FileFormat::Json,
+            r#"{"key25": {"full_screen": "value"}, "key26": {"full_screen": "value"}}"#,
+        );
+        let config = Config::builder().add_file(file.clone()).build().unwrap();
+        assert_eq!(
+            config.get_path("key25.full_screen"),
+            Some(&Value::from("from_env"))
+        );
+        assert_eq!(
+            config.get_path("key26.full_screen"),
+            Some(&Value::from("value"))
+        );
+
+        let config = Config::builder()
+            .add_file(file.clone())
+            .env_separator("_")
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get_path("key25.full_screen"),
+            Some(&Value::from("value"))
+        );
+        assert_eq!(
+            config.get_path("key26.full_screen"),
+            Some(&Value::from("value"))
+        );
+
+        let config = Config::builder()
+            .add_file(file)
+            .env_separator("")
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get_path("key25.full_screen"),
+            Some(&Value::from("from_env"))
+        );
+
+        unsafe {
+            std::env::remove_var("KEY25__FULL_SCREEN");
+            std::env::remove_var("KEY26_FULL_SCREEN");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_env_coerce_types() {
+        unsafe {
+            std::env::set_var("KEY27__PORT", "8080");
+            std::env::set_var("KEY27__DEBUG", "TRUE");
+            std::env::set_var("KEY27__RATIO", " 0.5 ");
+            std::env::set_var("KEY27__NAME", "42");
+            std::env::set_var("KEY27__RETRIES", "many");
+            std::env::set_var("KEY27__VERBOSE", "yes");
+            std::env::set_var("KEY27__QUIET", "Off");
+            std::env::set_var("KEY27__COLOR", "maybe");
+            std::env::set_var("KEY27__SEED", "18446744073709551615");
+        }
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"key27": {"port": 80, "debug": false, "ratio": 1.5, "name": "app", "retries": 3,
+                    "verbose": false, "quiet": true, "color": true, "seed": 1}}"#,
+            ))
+            .build()
+            .unwrap();
+        assert_eq!(config.get_path("key27.port"), Some(&Value::Int(8080)));
+        assert_eq!(config.get_path("key27.debug"), Some(&Value::Bool(true)));
+        assert_eq!(config.get_path("key27.ratio"), Some(&Value::Float(0.5)));
+        assert_eq!(config.get_path("key27.name"), Some(&Value::from("42")));
+        assert_eq!(config.get_path("key27.retries"), Some(&Value::from("many")));
+        assert_eq!(config.get_path("key27.verbose"), Some(&Value::Bool(true)));
+        assert_eq!(config.get_path("key27.quiet"), Some(&Value::Bool(false)));
+        assert_eq!(config.get_path("key27.color"), Some(&Value::from("maybe")));
+        assert_eq!(config.get_path("key27.seed"), Some(&Value::UInt(u64::MAX)));
+
+        unsafe {
+            std::env::remove_var("KEY27__PORT");
+            std::env::remove_var("KEY27__DEBUG");
+            std::env::remove_var("KEY27__RATIO");
+            std::env::remove_var("KEY27__NAME");
+            std::env::remove_var("KEY27__RETRIES");
+            std::env::remove_var("KEY27__VERBOSE");
+            std::env::remove_var("KEY27__QUIET");
+            std::env::remove_var("KEY27__COLOR");
+            std::env::remove_var("KEY27__SEED");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_get_with_source() {
+        unsafe {
+            std::env::set_var("KEY17", "from_env");
+        }
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key16\": \"value\", \"key17\": \"value\"}",
+            ))
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get_with_source("key16"),
+            Some((&Value::String("value".to_string()), "test_file"))
+        );
+        assert_eq!(
+            config.get_with_source("key17"),
+            Some((&Value::String("from_env".to_string()), "env"))
+        );
+        assert_eq!(config.get_with_source("missing"), None);
+
+        unsafe {
+            std::env::remove_var("KEY17");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "env", feature = "json"))]
+    fn test_env_overrides() {
+        unsafe {
+            std::env::set_var("KEY18", "from_env");
+        }
+
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                "{\"key18\": \"value\", \"key19\": \"value\"}",
+            ))
+            .build()
+            .unwrap();
+        let overrides = config.env_overrides();
+        assert!(overrides.contains(&"key18".to_string()));
+        assert!(!overrides.contains(&"key19".to_string()));
+
+        unsafe {
+            std::env::remove_var("KEY18");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_description() {
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Toml,
+                "# Port to listen on\nport = 8080\n\n[db]\n# Connection string\nurl = \"\"\n",
+            ))
+            .build()
+            .unwrap();
+        assert_eq!(config.description("port"), Some("Port to listen on"));
+        assert_eq!(config.description("db.url"), Some("Connection string"));
+        assert_eq!(config.description("db"), None);
+    }
+
+    #[test]
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    fn test_extend_from_str() {
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Toml,
+                "name = \"app\"\n\n[db]\nhost = \"localhost\"\nport = 5432\n",
+            ))
+            .build()
+            .unwrap();
+        config.set_value("name", "changed");
+        config
+            .extend_from_str(
+                FileFormat::Yaml,
+                "name: other\ndb:\n  port: 6432\n  user: admin\n",
+            )
+            .unwrap();
+
+        assert_eq!(
+            config.get("db"),
+            Some(&Value::Table(Map::from_iter([
+                ("host".to_string(), Value::from("localhost")),
+                ("port".to_string(), Value::Int(6432)),
+                ("user".to_string(), Value::from("admin")),
+            ])))
+        );
+        assert_eq!(config.get("name"), Some(&Value::from("changed")));
+        assert_eq!(config.get_with_source("db").unwrap().1, "extend");
+        assert_eq!(config.save(FileFormat::Yaml).unwrap(), "---\nname: changed");
+        assert!(
+            config
+                .extend_from_str(FileFormat::Yaml, "- not a table")
+                .is_err()
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "intern", feature = "json"))]
+    fn test_intern_strings() {
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"a": "debug", "b": {"level": "debug"}, "c": ["debug", "info"]}"#,
+            ))
+            .build()
+            .unwrap();
+        config.intern_strings();
+
+        let shared = |value: Option<&Value>| match value {
+            Some(Value::Shared(s)) => s.clone(),
+            other => panic!("expected a shared string, found {:?}", other),
+        };
+        let a = shared(config.get("a"));
+        let level = shared(config.get("b").and_then(|b| b.get("level")));
+        assert!(Arc::ptr_eq(&a, &level));
+        assert_eq!(config.get("a"), Some(&Value::from("debug")));
+        assert_eq!(config.get_enum("a", &["debug", "info"]).unwrap(), "debug");
+        assert_eq!(
+            serde_json::to_string(&config.to_json_value()).unwrap(),
+            r#"{"a":"debug","b":{"level":"debug"},"c":["debug","info"]}"#
+        );
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Level {
+            Debug,
+            Info,
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Levels {
+            a: Level,
+            c: Vec<Level>,
+        }
+        assert_eq!(
+            config.try_deserialize::<Levels>(),
+            Ok(Levels {
+                a: Level::Debug,
+                c: vec![Level::Debug, Level::Info],
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_collect_by_kind() {
+        let config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"port": 80, "name": "app", "db": {"pool": 4, "host": "h", "retry": {"count": 3, "delay": 0.5}}, "tags": [1, 2]}"#,
+            ))
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.collect_by_kind(ValueKind::Int),
+            vec![
+                ("db.pool".to_string(), &Value::Int(4)),
+                ("db.retry.count".to_string(), &Value::Int(3)),
+                ("port".to_string(), &Value::Int(80)),
+            ]
+        );
+        let strings: Vec<String> = config
+            .collect_by_kind(ValueKind::String)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(strings, vec!["db.host", "name"]);
+    }
+
+    #[test]
+    fn test_assert_eq_ignoring() {
+        let mut left = Config::builder().build().unwrap();
+        left.set_value("name", "app");
+        left.set_value(
+            "meta",
+            Map::from_iter([
+                ("generated_at".to_string(), Value::Int(1)),
+                ("version".to_string(), Value::Int(2)),
+            ]),
+        );
+        left.set_value("id", "a1");
+        let mut right = Config::builder().build().unwrap();
+        right.set_value("name", "app");
+        right.set_value(
+            "meta",
+            Map::from_iter([
+                ("generated_at".to_string(), Value::Int(9)),
+                ("version".to_string(), Value::Int(2)),
+            ]),
+        );
+        right.set_value("id", "b2");
+        left.assert_eq_ignoring(&right, &["meta.generated_at", "id"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Configs differ:\n  meta.version: 2 != 3\n  extra: <missing> != 1")]
+    fn test_assert_eq_ignoring_diff() {
+        let mut left = Config::builder().build().unwrap();
+        left.set_value(
+            "meta",
+            Map::from_iter([("version".to_string(), Value::Int(2))]),
+        );
+        let mut right = Config::builder().build().unwrap();
+        right.set_value(
+            "meta",
+            Map::from_iter([("version".to_string(), Value::Int(3))]),
+        );
+        right.set_value("extra", 1);
+        left.assert_eq_ignoring(&right, &["meta.ver"]);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_fill_from_template() {
+        let template = Config::builder()
+            .add_file(File::new_str(
+                "template.json",
+                FileFormat::Json,
+                r#"{"name": "default", "db": {"host": "localhost", "pool": {"size": 4}}, "debug": false}"#,
+            ))
+            .build()
+            .unwrap();
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "user.json",
+                FileFormat::Json,
+                r#"{"name": "app", "db": {"host": "db.local", "pool": 1}}"#,
+            ))
+            .build()
+            .unwrap();
+        config.fill_from_template(&template);
+
+        assert_eq!(config.get("name"), Some(&Value::from("app")));
+        assert_eq!(
+            config.get("db"),
+            Some(&Value::Table(Map::from_iter([
+                ("host".to_string(), Value::from("db.local")),
+                ("pool".to_string(), Value::Int(1)),
+            ])))
+        );
+        assert_eq!(
+            config.get_with_source("debug"),
+            Some((&Value::Bool(false), "template"))
+        );
+
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "user.json",
+                FileFormat::Json,
+                r#"{"db": {"pool": {}}}"#,
+            ))
+            .build()
+            .unwrap();
+        config.fill_from_template(&template);
+        assert_eq!(
+            config.get("db"),
+            template.get("db"),
+            "nested keys are filled"
+        );
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn test_decimal_comma() {
+        let build = |decimal_comma: bool| {
+            let mut config = Config::builder()
+                .decimal_comma(decimal_comma)
+                .build()
+                .unwrap();
+            config.set_value("pi", "3,14");
+            config.set_value("thousands", "1,000.5");
+            config.set_value("grouped", "1,500");
+            config.set_value("negative_grouped", "-12,500");
+            config.set_value("two_commas", "1,000,000");
+            config.set_value("small", "0,125");
+            config.set_value("native", 2.5);
+            config
+        };
+
+        let config = build(true);
+        assert_eq!(config.get_float("pi"), Ok(3.14));
+        assert_eq!(config.get_f64_in_range("pi", 0.0, 10.0), Ok(3.14));
+        assert_eq!(config.get_string("pi"), Ok("3,14".to_string()));
+        assert_eq!(config.get_float("small"), Ok(0.125));
+        assert_eq!(config.get_float("grouped"), Ok(1.5));
+        assert_eq!(config.get_float("negative_grouped"), Ok(-12.5));
+        assert_eq!(
+            config.get_float("two_commas"),
+            Err(ConfigError::Convert {
+                key: "two_commas".to_string(),
+                error: CannotConvert::new("String", "Float"),
+            })
+        );
+        assert_eq!(config.get_f64_clamped("pi", 0.0, 10.0), Ok(3.14));
+        assert_eq!(config.get_f64_clamped("native", 0.0, 10.0), Ok(2.5));
+        assert!(config.get_f64_clamped("thousands", 0.0, 10.0).is_err());
+        assert_eq!(config.get("pi"), Some(&Value::from("3,14")));
+
+        let config = build(false);
+        assert_eq!(
+            config.get_f64_clamped("pi", 0.0, 10.0),
+            Err(ConfigError::Convert {
+                key: "pi".to_string(),
+                error: CannotConvert::new("String", "Float"),
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_quoted_path_keys() {
+        let mut config = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"my.key": {"sub": 1, "other": 2}, "server": {"tls.v1": {"cert": "a.pem"}}}"#,
+            ))
+            .expect(r#""my.key".sub"#, ValueKind::Int)
+            .override_value(r#""my.key".other"#, 3)
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.get("my.key"),
+            Some(&Value::Table(Map::from_iter([
+                ("sub".to_string(), Value::Int(1)),
+                ("other".to_string(), Value::Int(3)),
+            ])))
+        );
+        assert_eq!(
+            config.remove_path(r#"server."tls.v1".cert"#),
+            Some(Value::from("a.pem"))
+        );
+
+        let result = Config::builder()
+            .add_file(File::new_str(
+                "test_file",
+                FileFormat::Json,
+                r#"{"my.key": {"sub": "1"}}"#,
+            ))
+            .expect(r#""my.key".sub"#, ValueKind::Int)
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_require_non_empty() {
+        let result = Config::builder().require_non_empty().build();
+        assert_eq!(result.err().unwrap(), ConfigError::EmptyConfig);
+
+        let config = Config::builder()
+            .override_value("key", 1)
+            .require_non_empty()
+            .build()
+            .unwrap();
+        assert_eq!(config.get("key"), Some(&Value::Int(1)));
+    }
+
+    #[test]
+    fn test_file_paths() {
+        let builder = Config::builder()
+            .add_file(File::new_str("base.json", FileFormat::Json, "{}"))
+            .add_file(File::new_str("local.yaml", FileFormat::Yaml, ""))
+            .add_file(File::new_str("override.toml", FileFormat::Toml, ""));
+        assert_eq!(
+            builder.file_paths(),
+            vec!["base.json", "local.yaml", "override.toml"]
+        );
+
+        let builder = Config::builder()
+            .add(File::new_str("base.json", FileFormat::Json, "{}"))
+            .add_file(File::new_str("local.yaml", FileFormat::Yaml, ""));
+        assert_eq!(builder.file_paths(), vec!["base.json", "local.yaml"]);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_builder_with_capacity() {
+        let build = |builder: ConfigBuilder| {
+            builder
+                .add_file(File::new_str(
+                    "base.json",
+                    FileFormat::Json,
+                    r#"{"a": 1, "b": {"c": [1, 2]}}"#,
+                ))
+                .add_file(File::new_str(
+                    "local.json",
+                    FileFormat::Json,
+                    r#"{"a": 2, "d": "x"}"#,
+                ))
+                .override_value("d", "y")
+                .build()
+                .unwrap()
+        };
+        let plain = build(Config::builder());
+        let sized = build(Config::builder_with_capacity(2, 64));
+        sized.assert_eq_ignoring(&plain, &[]);
+        assert_eq!(sized.get_with_source("a"), plain.get_with_source("a"));
+        assert_eq!(sized.get_with_source("d"), plain.get_with_source("d"));
+        assert!(sized.values.capacity() >= 64);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_build_keeps_defaults_only_when_written() {
+        let file = || {
+            File::new_str(
+                "base.json",
+                FileFormat::Json,
+                r#"{"lazy_defaults": {"a": 1, "b": 2}}"#,
+            )
+        };
+        let mut config = Config::builder().add_file(file()).build().unwrap();
+        assert_eq!(config.defaults, None);
+
+        config.set_path("lazy_defaults.a", Value::Int(10)).unwrap();
+        assert_eq!(
+            config
+                .defaults
+                .as_ref()
+                .and_then(|d| d.get("lazy_defaults")),
+            Some(&Value::Table(Map::from_iter([
+                ("a".to_string(), Value::Int(1)),
+                ("b".to_string(), Value::Int(2)),
+            ])))
+        );
+
+        let config = Config::builder()
+            .add_file(file())
+            .override_value("lazy_defaults.b", 20)
+            .build()
+            .unwrap();
+        assert!(config.defaults.is_some());
+        assert_eq!(config.get_path("lazy_defaults.b"), Some(&Value::Int(20)));
+    }
+
+    #[cfg(not(any(
+        feature = "ini",
+        feature = "json",
+        feature = "yaml",
+        feature = "toml",
+        feature = "ron"
+    )))]
+    mod core_only {
+        use super::*;
+
+        const FORMATS: [FileFormat; 5] = [
+            FileFormat::Ini,
+            FileFormat::Json,
+            FileFormat::Yaml,
+            FileFormat::Toml,
+            FileFormat::Ron,
+        ];
+
+        #[test]
+        fn test_core_only_config() {
+            let mut config = Config::builder().build().unwrap();
+            config.set("key", Value::Int(1));
+            assert_eq!(config.get("key").unwrap(), &Value::Int(1));
+        }
+
+        #[test]
+        fn test_core_only_formats_not_enabled() {
+            for format in FORMATS {
+                for content in ["content", "", " \n"] {
+                    let result = Config::builder()
+                        .add_file(File::new_str("test_file", format.clone(), content))
+                        .build();
+                    assert_eq!(
+                        result.err().unwrap(),
+                        ConfigError::parse(
+                            "test_file",
+                            ConfigError::FeatureDisabled(format.clone())
+                        )
+                    );
+                }
+                let result = save_map(&Map::new(), format.clone());
+                assert_eq!(result.unwrap_err(), ConfigError::FeatureDisabled(format));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "toml"))]
+    fn test_feature_disabled_names_feature() {
+        let expected = ConfigError::FeatureDisabled(FileFormat::Toml);
+        assert!(expected.to_string().contains(r#"features = ["toml"]"#));
+        let result = load_map("key = 1".to_string(), FileFormat::Toml);
+        assert_eq!(result.unwrap_err(), expected);
+        let result = save_map(&Map::new(), FileFormat::Toml);
+        assert_eq!(result.unwrap_err(), expected);
+        let result = File::new_str("test_file", FileFormat::Toml, "key = 1").parse();
+        assert_eq!(result.unwrap_err(), expected);
+    }
+
+    mod convert {
+        use super::*;
+
+        #[test]
+        #[cfg(all(feature = "json", feature = "yaml"))]
+        fn test_convert_json_to_yaml() {
+            let yaml = convert(
+                r#"{"key": "value", "list": [1, 2]}"#,
+                FileFormat::Json,
+                FileFormat::Yaml,
+            )
+            .unwrap();
+            assert_eq!(yaml, "---\nkey: value\nlist:\n  - 1\n  - 2");
+        }
+
+        #[test]
+        #[cfg(all(feature = "json", feature = "toml"))]
+        fn test_convert_null_to_toml() {
+            let toml = convert(r#"{"key": null}"#, FileFormat::Json, FileFormat::Toml);
+            assert!(toml.is_err());
+        }
+
+        #[test]
+        #[cfg(feature = "json")]
+        fn test_convert_json_to_json() {
+            let json = convert(r#"{"key": "value"}"#, FileFormat::Json, FileFormat::Json);
+            assert_eq!(json.unwrap(), r#"{"key":"value"}"#);
+        }
+
+        #[test]
+        #[cfg(not(feature = "json"))]
+        fn test_convert_not_enabled() {
+            let result = convert(r#"{"key": "value"}"#, FileFormat::Json, FileFormat::Json);
+            assert!(result.is_err());
+        }
+    }
+
+    mod serialize_deserialize {
+        use super::*;
+
+        /// `Value::Int(1)` and `Value::Float(1.0)` must stay distinct after a round-trip in
+        /// every format that can tell them apart. INI stores every value as a string.
+        #[cfg(any(feature = "json", feature = "yaml", feature = "toml", feature = "ron"))]
+        fn assert_numbers_round_trip(format: FileFormat) {
+            #[allow(unused_mut)]
+            let mut map = Map::from_iter(vec![
+                ("int".to_string(), Value::Int(1)),
+                ("float".to_string(), Value::Float(1.0)),
+                ("fraction".to_string(), Value::Float(1.5)),
+            ]);
+            let saved = save_map(&map, format.clone()).unwrap();
+            let loaded = load_map(saved, format).unwrap();
+            assert_eq!(loaded, map);
+        }
+
+        #[test]
+        #[cfg(feature = "json")]
+        fn test_deserialize_u64_max_json() {
+            let map = load_map(
+                r#"{"big": 18446744073709551615}"#.to_string(),
+                FileFormat::Json,
+            );
+            assert_eq!(map.unwrap()["big"], Value::from(u64::MAX));
+        }
+
+        #[test]
+        #[cfg(feature = "yaml")]
+        fn test_deserialize_u64_max_yaml() {
+            let map = load_map("big: 18446744073709551615".to_string(), FileFormat::Yaml);
+            assert_eq!(map.unwrap()["big"], Value::from(u64::MAX));
+        }
+
+        #[test]
+        #[cfg(feature = "ron")]
+        fn test_deserialize_u64_max_ron() {
+            let map = load_map("(big: 18446744073709551615)".to_string(), FileFormat::Ron);
+            assert_eq!(map.unwrap()["big"], Value::from(u64::MAX));
+        }
+
+        #[cfg(any(feature = "json", feature = "yaml", feature = "ron"))]
+        fn assert_u64_max_round_trip(format: FileFormat) {
+            let map = Map::from_iter(vec![("big".to_string(), Value::from(u64::MAX))]);
+            assert_eq!(map["big"], Value::UInt(u64::MAX));
+            let saved = save_map(&map, format.clone()).unwrap();
+            let loaded = load_map(saved, format).unwrap();
+            assert_eq!(loaded, map);
+            let big: u64 = loaded["big"].clone().try_into().unwrap();
+            assert_eq!(big, u64::MAX);
+        }
+
+        #[test]
+        #[cfg(feature = "json")]
+        fn test_u64_max_round_trip_json() {
+            assert_u64_max_round_trip(FileFormat::Json);
+        }
+
+        #[test]
+        #[cfg(feature = "yaml")]
+        fn test_u64_max_round_trip_yaml() {
+            assert_u64_max_round_trip(FileFormat::Yaml);
+        }
+
+        #[test]
+        #[cfg(feature = "ron")]
+        fn test_u64_max_round_trip_ron() {
+            assert_u64_max_round_trip(FileFormat::Ron);
+        }
+
+        #[test]
+        #[cfg(feature = "toml")]
+        fn test_u64_max_toml_unsupported() {
+            let map = Map::from_iter(vec![("big".to_string(), Value::from(u64::MAX))]);
+            assert!(save_map(&map, FileFormat::Toml).is_err());
+        }
+
+        #[cfg(all(feature = "i128", any(feature = "yaml", feature = "ron")))]
+        fn assert_big_int_round_trip(format: FileFormat, value: i128) {
+            let map = Map::from_iter(vec![("big".to_string(), Value::from(value))]);
+            assert_eq!(map["big"], Value::BigInt(value));
+            let saved = save_map(&map, format.clone()).unwrap();
+            let loaded = load_map(saved, format).unwrap();
+            assert_eq!(loaded, map);
+        }
+
+        #[test]
+        #[cfg(all(feature = "i128", feature = "json"))]
+        fn test_big_int_json_unsupported() {
+            let map = Map::from_iter(vec![("big".to_string(), Value::from(i128::MAX))]);
+            assert!(save_map(&map, FileFormat::Json).is_err());
+        }
+
+        #[test]
+        #[cfg(all(feature = "i128", feature = "yaml"))]
+        fn test_big_int_round_trip_yaml() {
+            assert_big_int_round_trip(FileFormat::Yaml, i128::MAX);
+            assert_big_int_round_trip(FileFormat::Yaml, i128::MIN);
+        }
+
+        #[test]
+        #[cfg(all(feature = "i128", feature = "ron"))]
+        fn test_big_int_round_trip_ron() {
+            assert_big_int_round_trip(FileFormat::Ron, i128::MAX);
+            assert_big_int_round_trip(FileFormat::Ron, i128::MIN);
+        }
+
+        #[test]
+        #[cfg(all(feature = "i128", feature = "toml"))]
+        fn test_big_int_toml_unsupported() {
+            let map = Map::from_iter(vec![("big".to_string(), Value::from(i128::MAX))]);
+            assert!(save_map(&map, FileFormat::Toml).is_err());
+        }
+
+        /// TOML integers are 64-bit signed by spec, so larger literals are a parse error.
+        #[test]
+        #[cfg(feature = "toml")]
+        fn test_deserialize_u64_max_toml() {
+            let map = load_map("big = 18446744073709551615".to_string(), FileFormat::Toml);
+            assert!(map.is_err());
+        }
+
+        #[test]
+        #[cfg(feature = "json")]
+        fn test_numbers_round_trip_json() {
+            assert_numbers_round_trip(FileFormat::Json);
+        }
+
+        #[test]
+        #[cfg(feature = "yaml")]
+        fn test_numbers_round_trip_yaml() {
+            assert_numbers_round_trip(FileFormat::Yaml);
+        }
+
+        #[test]
+        #[cfg(feature = "toml")]
+        fn test_numbers_round_trip_toml() {
+            assert_numbers_round_trip(FileFormat::Toml);
+        }
+
+        #[test]
+        #[cfg(feature = "ron")]
+        fn test_numbers_round_trip_ron() {
+            assert_numbers_round_trip(FileFormat::Ron);
+        }
+
+        #[test]
+        fn test_deserialize_whitespace() {
+            for format in [
+                FileFormat::Ini,
+                FileFormat::Yaml,
+                FileFormat::Toml,
+                FileFormat::Ron,
+                FileFormat::Json,
+            ] {
+                let map = load_map("   \n\t".to_string(), format.clone());
+                let expected = match format {
+                    _ if !format.is_enabled() => Err(ConfigError::FeatureDisabled(format)),
+                    FileFormat::Json => Err(ConfigError::Empty),
+                    _ => Ok(Map::new()),
+                };
+                assert_eq!(map, expected);
+            }
+        }
+
+        #[test]
+        #[cfg(feature = "ini")]
+        fn test_deserialize_ini() {
+            let ini = r#"[section]
+key: "value""#;
+            let map = load_map(ini.to_string(), FileFormat::Ini);
+            assert!(map.is_ok());
+        }
+
+        #[test]
+        #[cfg(feature = "ini")]
+        fn test_serialize_ini() {
+            let ini = "name=app\n\n[server]\nhost=localhost\nport=80\n";
+            let map = load_map(ini.to_string(), FileFormat::Ini).unwrap();
+            assert_eq!(save_map(&map, FileFormat::Ini).unwrap(), ini);
+            assert_eq!(save_map(&Map::new(), FileFormat::Ini).unwrap(), "");
+        }
+
+        #[test]
+        #[cfg(all(feature = "toml", feature = "json", feature = "yaml"))]
+        fn test_toml_datetime_to_other_formats() {
+            let map = load_map("at = 2023-01-01T00:00:00Z".to_string(), FileFormat::Toml).unwrap();
+            assert_eq!(
+                save_map(&map, FileFormat::Json).unwrap(),
+                r#"{"at":"2023-01-01T00:00:00Z"}"#
+            );
+            let yaml = save_map(&map, FileFormat::Yaml).unwrap();
+            assert_eq!(
+                load_map(yaml, FileFormat::Yaml).unwrap().get("at"),
+                Some(&Value::String("2023-01-01T00:00:00Z".to_string()))
+            );
         }
 
         #[test]
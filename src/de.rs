@@ -0,0 +1,278 @@
+//! Serde bridge for reading typed structs out of values
+
+use serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{self, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::error::ConfigError;
+use crate::value::{Array, Table, Value};
+
+/// Reads a value from any self-describing format: maps become tables, sequences become arrays
+/// and `null` becomes `None`.
+impl<'de> de::Deserialize<'de> for Value {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("any config value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::None)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        de::Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut array = Array::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            array.push(value);
+        }
+        Ok(Value::Array(array))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut table = Table::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((key, value)) = map.next_entry()? {
+            table.insert(key, value);
+        }
+        Ok(Value::Table(table))
+    }
+}
+
+impl de::Error for ConfigError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        ConfigError::Deserialize(msg.to_string())
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ConfigError> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = ConfigError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::None => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Int(i) => visitor.visit_i64(i),
+            Value::UInt(u) => visitor.visit_u64(u),
+            #[cfg(feature = "i128")]
+            Value::BigInt(i) => visitor.visit_i128(i),
+            Value::Float(f) => visitor.visit_f64(f),
+            Value::String(s) => visitor.visit_string(s),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => visitor.visit_str(&s),
+            Value::Datetime(dt) => visitor.visit_string(dt),
+            Value::Array(array) => {
+                let mut seq = SeqDeserializer::new(array.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Table(table) => {
+                let mut map = MapDeserializer::new(table.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::None => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::String(s) => visitor.visit_enum(s.into_deserializer()),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => visitor.visit_enum(s.to_string().into_deserializer()),
+            Value::Table(table) if table.len() == 1 => visitor.visit_enum(
+                MapAccessDeserializer::new(MapDeserializer::new(table.into_iter())),
+            ),
+            value => Err(de::Error::invalid_type(
+                de::Unexpected::Other(value.kind().as_str()),
+                &"a string or a table with a single key",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::value::Map;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Mode {
+        Fast,
+        Slow { delay: u32 },
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Settings {
+        name: String,
+        port: u16,
+        ratio: f64,
+        tags: Vec<String>,
+        mode: Mode,
+        fallback: Option<Mode>,
+    }
+
+    #[test]
+    fn test_deserialize_struct() {
+        let value = Value::Table(Map::from_iter(vec![
+            ("name".to_string(), Value::String("app".to_string())),
+            ("port".to_string(), Value::Int(8080)),
+            ("ratio".to_string(), Value::Int(1)),
+            (
+                "tags".to_string(),
+                Value::Array(vec![Value::String("a".to_string())]),
+            ),
+            ("mode".to_string(), Value::String("Fast".to_string())),
+            (
+                "fallback".to_string(),
+                Value::Table(Map::from_iter(vec![(
+                    "Slow".to_string(),
+                    Value::Table(Map::from_iter(vec![("delay".to_string(), Value::Int(5))])),
+                )])),
+            ),
+        ]));
+        let settings = Settings::deserialize(value).unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                name: "app".to_string(),
+                port: 8080,
+                ratio: 1.0,
+                tags: vec!["a".to_string()],
+                mode: Mode::Fast,
+                fallback: Some(Mode::Slow { delay: 5 }),
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_value() {
+        let value = Value::Table(Map::from_iter(vec![
+            ("name".to_string(), Value::from("app")),
+            ("none".to_string(), Value::None),
+            (
+                "list".to_string(),
+                Value::Array(vec![Value::Int(-1), Value::Float(0.5)]),
+            ),
+        ]));
+        assert_eq!(Value::deserialize(value.clone()).unwrap(), value);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_deserialize_value_from_json() {
+        let value: Value =
+            serde_json::from_str(r#"{"a": [1, null, "x"], "b": {"c": true}}"#).unwrap();
+        assert_eq!(
+            value,
+            Value::Table(Map::from_iter(vec![
+                (
+                    "a".to_string(),
+                    Value::Array(vec![Value::Int(1), Value::None, Value::from("x")]),
+                ),
+                (
+                    "b".to_string(),
+                    Value::Table(Map::from_iter(vec![("c".to_string(), Value::Bool(true))])),
+                ),
+            ]))
+        );
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"a":[1,null,"x"],"b":{"c":true}}"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "intern")]
+    fn test_deserialize_shared_enum() {
+        let value = Value::Shared(std::sync::Arc::from("Fast"));
+        assert_eq!(Mode::deserialize(value), Ok(Mode::Fast));
+    }
+
+    #[test]
+    fn test_deserialize_out_of_range() {
+        let result = u16::deserialize(Value::Int(70000));
+        assert!(matches!(result, Err(ConfigError::Deserialize(_))));
+    }
+}
@@ -1,3 +1,9 @@
+#[cfg(feature = "std")]
+use crate::file::FileFormat;
+use alloc::string::{String, ToString};
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+
 /// Error to indicate that a conversion between two types is not possible
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CannotConvert {
@@ -12,14 +18,198 @@ impl CannotConvert {
             to: to.to_string(),
         }
     }
+
+    /// Kind of the value that could not be converted, like `Array`
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    /// Type the value was converted to, like `Int`
+    pub fn to(&self) -> &str {
+        &self.to
+    }
 }
 
-impl std::fmt::Display for CannotConvert {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for CannotConvert {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "Cannot convert {} to {}", self.from, self.to)
     }
 }
 
+impl core::error::Error for CannotConvert {}
+
+/// Error returned when building, loading, saving or reading a value from a config fails
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Key is not present in the config
+    MissingKey(String),
+    /// Value under the key cannot be converted to the requested type
+    Convert { key: String, error: CannotConvert },
+    /// Value under the key is not one of the allowed variants
+    InvalidVariant {
+        key: String,
+        value: String,
+        allowed: Vec<String>,
+    },
+    /// Value under the key is outside of the allowed inclusive range
+    OutOfRange {
+        key: String,
+        value: String,
+        min: String,
+        max: String,
+    },
+    /// Value under the key is not a valid IP or socket address
+    InvalidAddress {
+        key: String,
+        value: String,
+        error: std::net::AddrParseError,
+    },
+    /// Value under the key is not a valid integer range
+    InvalidRange {
+        key: String,
+        value: String,
+        reason: String,
+    },
+    /// File content could not be parsed, `source` is the error from the format
+    Parse {
+        path: String,
+        source: Box<ConfigError>,
+    },
+    /// Content is not valid in the format, or values can't be written in it
+    Format { format: FileFormat, message: String },
+    /// Reading a file or directory failed
+    Io {
+        kind: std::io::ErrorKind,
+        message: String,
+    },
+    /// The format of a file can't be told from its extension
+    UnsupportedFormat { path: String },
+    /// Several files set the same keys to different values, with `ConflictPolicy::Error`
+    Conflicts(Vec<String>),
+    /// Values don't have the kinds declared with `ConfigBuilder::expect()`
+    TypeMismatch(Vec<String>),
+    /// Nothing is left after building, with `ConfigBuilder::require_non_empty()`
+    EmptyConfig,
+    /// Content is empty or only whitespace, for JSON which has no comments
+    Empty,
+    /// File content is not valid UTF-8
+    NotUtf8 { path: String },
+    /// Value cannot be deserialized into the requested type
+    Deserialize(String),
+    /// Value cannot be serialized into a config value
+    Serialize(String),
+    /// Format is not compiled in, its Cargo feature has to be enabled
+    FeatureDisabled(FileFormat),
+    /// No format was set with `ConfigBuilder::save_format()`
+    NoSaveFormat,
+}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::MissingKey(key) => write!(f, "Key {} is missing", key),
+            ConfigError::Convert { key, error } => write!(f, "Key {}: {}", key, error),
+            ConfigError::InvalidVariant {
+                key,
+                value,
+                allowed,
+            } => write!(
+                f,
+                "Key {}: invalid value {}, expected one of: {}",
+                key,
+                value,
+                allowed.join(", ")
+            ),
+            ConfigError::OutOfRange {
+                key,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "Key {}: value {} is out of range {}..={}",
+                key, value, min, max
+            ),
+            ConfigError::InvalidAddress { key, value, error } => {
+                write!(
+                    f,
+                    "Key {}: {} is not a valid address: {}",
+                    key, value, error
+                )
+            }
+            ConfigError::InvalidRange { key, value, reason } => {
+                write!(f, "Key {}: {} is not a valid range, {}", key, value, reason)
+            }
+            ConfigError::Parse { path, source } => {
+                write!(f, "Failed to parse file {}: {}", path, source)
+            }
+            ConfigError::Format { message, .. } => write!(f, "{}", message),
+            ConfigError::Io { message, .. } => write!(f, "{}", message),
+            ConfigError::UnsupportedFormat { path } => {
+                write!(f, "Unsupported or missing file extension in {}", path)
+            }
+            ConfigError::Conflicts(conflicts) => {
+                write!(f, "Conflicting keys: {}", conflicts.join("; "))
+            }
+            ConfigError::TypeMismatch(mismatches) => {
+                write!(f, "Type mismatch: {}", mismatches.join("; "))
+            }
+            ConfigError::EmptyConfig => write!(
+                f,
+                "Config is empty after applying all files, changes and overrides"
+            ),
+            ConfigError::Empty => write!(f, "Empty content"),
+            ConfigError::NotUtf8 { path } => write!(f, "File {} is not valid UTF-8", path),
+            ConfigError::Deserialize(message) => write!(f, "Failed to deserialize: {}", message),
+            ConfigError::Serialize(message) => write!(f, "Failed to serialize: {}", message),
+            ConfigError::FeatureDisabled(format) => write!(
+                f,
+                "{} format feature is not enabled, enable it with `ronf = {{ features = [\"{}\"] }}`",
+                format.to_string().to_uppercase(),
+                format
+            ),
+            ConfigError::NoSaveFormat => write!(f, "No save format is set"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Convert { error, .. } => Some(error),
+            ConfigError::InvalidAddress { error, .. } => Some(error),
+            ConfigError::Parse { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ConfigError {
+    /// Wraps an IO error, keeping its kind and prefixing its message with `context`
+    pub(crate) fn io(context: impl std::fmt::Display, error: std::io::Error) -> Self {
+        ConfigError::Io {
+            kind: error.kind(),
+            message: format!("{}: {}", context, error),
+        }
+    }
+
+    /// Wraps an error from parsing the file at `path`
+    pub(crate) fn parse(path: &str, source: ConfigError) -> Self {
+        ConfigError::Parse {
+            path: path.to_string(),
+            source: Box::new(source),
+        }
+    }
+}
+
+/// Shorter name for `ConfigError`, the error type of every fallible function in the crate
+#[cfg(feature = "std")]
+pub type Error = ConfigError;
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -29,4 +219,95 @@ mod test {
         let error = CannotConvert::new("String", "Int");
         assert_eq!(error.to_string(), "Cannot convert String to Int");
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_cannot_convert_error() {
+        let error: Box<dyn std::error::Error> = Box::new(CannotConvert::new("Array", "Int"));
+        assert_eq!(error.to_string(), "Cannot convert Array to Int");
+        let error = error.downcast::<CannotConvert>().unwrap();
+        assert_eq!(error.from(), "Array");
+        assert_eq!(error.to(), "Int");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_config_error_display() {
+        let error = ConfigError::MissingKey("key".to_string());
+        assert_eq!(error.to_string(), "Key key is missing");
+        let error = ConfigError::Convert {
+            key: "key".to_string(),
+            error: CannotConvert::new("Int", "Path"),
+        };
+        assert_eq!(error.to_string(), "Key key: Cannot convert Int to Path");
+        let error = ConfigError::InvalidVariant {
+            key: "key".to_string(),
+            value: "c".to_string(),
+            allowed: vec!["a".to_string(), "b".to_string()],
+        };
+        assert_eq!(
+            error.to_string(),
+            "Key key: invalid value c, expected one of: a, b"
+        );
+        let error = ConfigError::OutOfRange {
+            key: "key".to_string(),
+            value: "11".to_string(),
+            min: "1".to_string(),
+            max: "10".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Key key: value 11 is out of range 1..=10"
+        );
+        assert_eq!(ConfigError::Empty.to_string(), "Empty content");
+        assert_eq!(
+            ConfigError::NoSaveFormat.to_string(),
+            "No save format is set"
+        );
+        assert_eq!(
+            ConfigError::Deserialize("missing field `port`".to_string()).to_string(),
+            "Failed to deserialize: missing field `port`"
+        );
+        assert_eq!(
+            ConfigError::Serialize("unsupported".to_string()).to_string(),
+            "Failed to serialize: unsupported"
+        );
+        assert_eq!(
+            ConfigError::FeatureDisabled(FileFormat::Toml).to_string(),
+            "TOML format feature is not enabled, enable it with `ronf = { features = [\"toml\"] }`"
+        );
+        let error = ConfigError::NotUtf8 {
+            path: "config.json".to_string(),
+        };
+        assert_eq!(error.to_string(), "File config.json is not valid UTF-8");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_config_error_source() {
+        use std::error::Error;
+
+        let error = ConfigError::parse(
+            "config.toml",
+            ConfigError::FeatureDisabled(FileFormat::Toml),
+        );
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            ConfigError::FeatureDisabled(FileFormat::Toml).to_string()
+        );
+        assert!(
+            error
+                .to_string()
+                .starts_with("Failed to parse file config.toml: ")
+        );
+        let error = ConfigError::Convert {
+            key: "key".to_string(),
+            error: CannotConvert::new("Int", "Path"),
+        };
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            "Cannot convert Int to Path"
+        );
+        assert!(ConfigError::Empty.source().is_none());
+    }
 }
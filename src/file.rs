@@ -1,5 +1,7 @@
 //! File handling
 
+use crate::error::ConfigError;
+use crate::path::split_path;
 use crate::value::{Map, Value};
 
 /// Supported file formats.
@@ -24,6 +26,31 @@ impl FileFormat {
             _ => None,
         }
     }
+
+    /// Whether the feature for this format is compiled in.
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            FileFormat::Ini => cfg!(feature = "ini"),
+            FileFormat::Json => cfg!(feature = "json"),
+            FileFormat::Yaml => cfg!(feature = "yaml"),
+            FileFormat::Toml => cfg!(feature = "toml"),
+            FileFormat::Ron => cfg!(feature = "ron"),
+        }
+    }
+
+    /// All formats whose feature is compiled in.
+    pub fn enabled() -> Vec<FileFormat> {
+        [
+            FileFormat::Ini,
+            FileFormat::Json,
+            FileFormat::Yaml,
+            FileFormat::Toml,
+            FileFormat::Ron,
+        ]
+        .into_iter()
+        .filter(FileFormat::is_enabled)
+        .collect()
+    }
 }
 
 impl std::fmt::Display for FileFormat {
@@ -38,6 +65,24 @@ impl std::fmt::Display for FileFormat {
     }
 }
 
+/// How keys repeated within the same INI section are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatedKeyPolicy {
+    /// Only the last value is kept
+    #[default]
+    Last,
+    /// All values are collected into an array
+    Array,
+}
+
+/// Options that change how file content is parsed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ParseOptions {
+    pub ini_repeated_keys: RepeatedKeyPolicy,
+    pub json_allow_comments: bool,
+    pub preserve_strings: Vec<String>,
+}
+
 /// Representation of a configuration file.
 #[derive(Debug, Clone)]
 pub struct File {
@@ -65,87 +110,291 @@ impl File {
         }
     }
 
+    /// Create a new file from raw bytes, failing if they are not valid UTF-8.
+    pub fn from_bytes(
+        path: String,
+        format: FileFormat,
+        bytes: Vec<u8>,
+    ) -> Result<Self, ConfigError> {
+        let content =
+            String::from_utf8(bytes).map_err(|_| ConfigError::NotUtf8 { path: path.clone() })?;
+        Ok(File::new(path, format, content))
+    }
+
+    /// Create a new file reading all content from a reader, like a `TcpStream` or an HTTP
+    /// response body from any client.
+    /// ```rust
+    /// #[cfg(feature = "json")]
+    /// {
+    /// use ronf::{Config, File, FileFormat};
+    /// // Stands in for a response body, e.g. `reqwest::blocking::get(url)?`.
+    /// let body = std::io::Cursor::new(b"{\"key\": \"value\"}".to_vec());
+    /// let file = File::from_reader("https://example.com/config.json".to_string(), FileFormat::Json, body)
+    ///     .unwrap();
+    /// let config = Config::builder().add_file(file).build().unwrap();
+    /// println!("\"key\": {}", config.get("key").unwrap());
+    /// }
+    /// ```
+    pub fn from_reader<R: std::io::Read>(
+        path: String,
+        format: FileFormat,
+        mut reader: R,
+    ) -> Result<Self, ConfigError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .map_err(|e| ConfigError::io(format!("Failed to read {}", path), e))?;
+        File::from_bytes(path, format, bytes)
+    }
+
     /// Create a new file from a path, reading the content from the file.
     #[cfg(feature = "read_file")]
-    pub fn from_path(path: String) -> Result<Self, String> {
-        let extension = path
+    pub fn from_path(path: String) -> Result<Self, ConfigError> {
+        let format = path
             .rsplit_once('.')
-            .and_then(|(_, ext)| if ext.is_empty() { None } else { Some(ext) })
-            .ok_or_else(|| format!("Failed to get file extension from {}", path))?;
-        let format = FileFormat::from_extension(extension)
-            .ok_or_else(|| format!("Unsupported file extension: {}", extension))?;
+            .and_then(|(_, ext)| FileFormat::from_extension(ext))
+            .ok_or_else(|| ConfigError::UnsupportedFormat { path: path.clone() })?;
 
-        let content = std::fs::read_to_string(&path)
-            .map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+        let content = read_content(&path)?;
 
         Ok(File::new(path.clone(), format, content))
     }
 
     /// Create a new file from a path and format, reading the content from the file.
     #[cfg(feature = "read_file")]
-    pub fn from_path_format(path: String, format: FileFormat) -> Result<Self, String> {
-        let content = std::fs::read_to_string(&path)
-            .map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+    pub fn from_path_format(path: String, format: FileFormat) -> Result<Self, ConfigError> {
+        let content = read_content(&path)?;
 
         Ok(File::new(path.clone(), format, content))
     }
 
     /// Parse the content of the file to be used in the Config.
-    pub fn parse(&self) -> Result<Map<String, Value>, String> {
+    pub fn parse(&self) -> Result<Map<String, Value>, ConfigError> {
+        self.parse_with(&ParseOptions::default())
+    }
+
+    /// Parse the content of the file with the given parse options.
+    pub(crate) fn parse_with(
+        &self,
+        options: &ParseOptions,
+    ) -> Result<Map<String, Value>, ConfigError> {
+        self.parse_nested(options, None)
+    }
+
+    /// Like `parse_with()`, but nests the parsed content under `key`, so `port = 1` in the file
+    /// is read as `key.port`.
+    pub(crate) fn parse_nested(
+        &self,
+        options: &ParseOptions,
+        key: Option<&str>,
+    ) -> Result<Map<String, Value>, ConfigError> {
+        let mut map = parse_content(self.content.clone(), &self.format, options)?;
+        if let Some(key) = key {
+            map = Map::from_iter([(key.to_string(), Value::Table(map))]);
+        }
+        for path in options.preserve_strings.iter() {
+            let keys = split_path(path);
+            let value = match get_in_mut(&mut map, &keys) {
+                Some(value) if value.is_number() => value,
+                _ => continue,
+            };
+            let file_keys = &keys[key.is_some() as usize..];
+            let text = self
+                .raw_scalar(file_keys)
+                .unwrap_or_else(|| value.to_string());
+            *value = Value::String(text);
+        }
+        Ok(map)
+    }
+
+    /// The exact text of the scalar at `keys`, for formats that keep it after parsing.
+    fn raw_scalar(&self, _keys: &[String]) -> Option<String> {
         match self.format {
-            FileFormat::Ini => {
-                #[cfg(feature = "ini")]
-                {
-                    crate::format::ini::deserialize(self.content.clone())
-                }
+            #[cfg(feature = "yaml")]
+            FileFormat::Yaml => crate::format::yaml::raw_scalar(&self.content, _keys),
+            #[cfg(feature = "toml")]
+            FileFormat::Toml => crate::format::toml::raw_scalar(&self.content, _keys),
+            _ => None,
+        }
+    }
 
-                #[cfg(not(feature = "ini"))]
-                Err("INI format feature is not enabled".to_string())
+    /// Descriptions taken from the comments right above each key, for formats that keep them.
+    pub(crate) fn descriptions(&self) -> Map<String, String> {
+        match self.format {
+            #[cfg(feature = "toml")]
+            FileFormat::Toml => crate::format::toml::descriptions(&self.content),
+            _ => Map::new(),
+        }
+    }
+}
+
+fn get_in_mut<'a>(map: &'a mut Map<String, Value>, keys: &[String]) -> Option<&'a mut Value> {
+    let (first, rest) = keys.split_first()?;
+    rest.iter()
+        .try_fold(map.get_mut(first)?, |value, key| value.get_mut(key))
+}
+
+/// Parse content in the given format. A leading byte order mark is skipped. Blank content is an
+/// empty map for formats with comments, and `ConfigError::Empty` for strict JSON. Formats whose
+/// feature is disabled fail with `ConfigError::FeatureDisabled`, blank or not.
+pub(crate) fn parse_content(
+    content: String,
+    format: &FileFormat,
+    options: &ParseOptions,
+) -> Result<Map<String, Value>, ConfigError> {
+    let content = match content.strip_prefix('\u{feff}') {
+        Some(content) => content.to_string(),
+        None => content,
+    };
+    if format.is_enabled() && content.trim().is_empty() {
+        return match format {
+            FileFormat::Json if !options.json_allow_comments => Err(ConfigError::Empty),
+            _ => Ok(Map::new()),
+        };
+    }
+
+    match format {
+        FileFormat::Ini => {
+            #[cfg(feature = "ini")]
+            {
+                crate::format::ini::deserialize(content, options.ini_repeated_keys)
+                    .map_err(|message| format_error(FileFormat::Ini, message))
             }
-            FileFormat::Json => {
-                #[cfg(feature = "json")]
-                {
-                    crate::format::json::deserialize(self.content.clone())
+
+            #[cfg(not(feature = "ini"))]
+            Err(ConfigError::FeatureDisabled(FileFormat::Ini))
+        }
+        FileFormat::Json => {
+            #[cfg(feature = "json")]
+            {
+                let content = match options.json_allow_comments {
+                    true => crate::format::json::strip_comments(&content),
+                    false => content,
+                };
+                if content.trim().is_empty() {
+                    return Ok(Map::new());
                 }
+                crate::format::json::deserialize(content)
+                    .map_err(|message| format_error(FileFormat::Json, message))
+            }
 
-                #[cfg(not(feature = "json"))]
-                Err("JSON format feature is not enabled".to_string())
+            #[cfg(not(feature = "json"))]
+            Err(ConfigError::FeatureDisabled(FileFormat::Json))
+        }
+        FileFormat::Yaml => {
+            #[cfg(feature = "yaml")]
+            {
+                crate::format::yaml::deserialize(content)
+                    .map_err(|message| format_error(FileFormat::Yaml, message))
             }
-            FileFormat::Yaml => {
-                #[cfg(feature = "yaml")]
-                {
-                    crate::format::yaml::deserialize(self.content.clone())
-                }
 
-                #[cfg(not(feature = "yaml"))]
-                Err("YAML format feature is not enabled".to_string())
+            #[cfg(not(feature = "yaml"))]
+            Err(ConfigError::FeatureDisabled(FileFormat::Yaml))
+        }
+        FileFormat::Toml => {
+            #[cfg(feature = "toml")]
+            {
+                crate::format::toml::deserialize(content)
+                    .map_err(|message| format_error(FileFormat::Toml, message))
             }
-            FileFormat::Toml => {
-                #[cfg(feature = "toml")]
-                {
-                    crate::format::toml::deserialize(self.content.clone())
-                }
 
-                #[cfg(not(feature = "toml"))]
-                Err("TOML format feature is not enabled".to_string())
+            #[cfg(not(feature = "toml"))]
+            Err(ConfigError::FeatureDisabled(FileFormat::Toml))
+        }
+        FileFormat::Ron => {
+            #[cfg(feature = "ron")]
+            {
+                crate::format::ron::deserialize(content)
+                    .map_err(|message| format_error(FileFormat::Ron, message))
             }
-            FileFormat::Ron => {
-                #[cfg(feature = "ron")]
-                {
-                    crate::format::ron::deserialize(self.content.clone())
-                }
 
-                #[cfg(not(feature = "ron"))]
-                Err("RON format feature is not enabled".to_string())
+            #[cfg(not(feature = "ron"))]
+            Err(ConfigError::FeatureDisabled(FileFormat::Ron))
+        }
+    }
+}
+
+/// Wraps an error message from one of the format modules.
+#[cfg(any(
+    feature = "ini",
+    feature = "json",
+    feature = "yaml",
+    feature = "toml",
+    feature = "ron"
+))]
+pub(crate) fn format_error(format: FileFormat, message: String) -> ConfigError {
+    ConfigError::Format { format, message }
+}
+
+/// Calls `f` for each record of the array at the dotted `key` in the file at `path`.
+///
+/// JSON files are streamed so only one record is in memory at a time, other formats are
+/// parsed whole first.
+#[cfg(feature = "read_file")]
+pub fn stream_records(
+    path: &str,
+    format: FileFormat,
+    key: &str,
+    mut f: impl FnMut(Value),
+) -> Result<(), ConfigError> {
+    let keys = crate::path::split_path(key);
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    let found = match format {
+        #[cfg(feature = "json")]
+        FileFormat::Json => {
+            let file = std::fs::File::open(path)
+                .map_err(|e| ConfigError::io(format!("Failed to read file {}", path), e))?;
+            crate::format::json::stream_array(std::io::BufReader::new(file), &keys, &mut f)
+                .map_err(|message| format_error(FileFormat::Json, message))?
+        }
+        format => {
+            let map = File::from_path_format(path.to_string(), format)?.parse()?;
+            let mut value = map.get(keys[0]);
+            for key in &keys[1..] {
+                value = value.and_then(|v| v.get(key));
+            }
+            if let Some(Value::Array(records)) = value {
+                records.iter().cloned().for_each(&mut f);
             }
+            value.map(Value::kind)
         }
+    };
+    match found {
+        Some(crate::value::ValueKind::Array) => Ok(()),
+        Some(kind) => Err(ConfigError::Convert {
+            key: key.to_string(),
+            error: crate::error::CannotConvert::new(kind.as_str(), "Array"),
+        }),
+        None => Err(ConfigError::MissingKey(key.to_string())),
     }
 }
 
+/// Reads a file as bytes and validates it separately, so invalid UTF-8 is not reported as
+/// a generic IO error.
+#[cfg(feature = "read_file")]
+fn read_content(path: &str) -> Result<String, ConfigError> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| ConfigError::io(format!("Failed to read file {}", path), e))?;
+    String::from_utf8(bytes).map_err(|_| ConfigError::NotUtf8 {
+        path: path.to_string(),
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_enabled_formats() {
+        let enabled = FileFormat::enabled();
+        assert_eq!(enabled.contains(&FileFormat::Ini), cfg!(feature = "ini"));
+        assert_eq!(enabled.contains(&FileFormat::Json), cfg!(feature = "json"));
+        assert_eq!(enabled.contains(&FileFormat::Yaml), cfg!(feature = "yaml"));
+        assert_eq!(enabled.contains(&FileFormat::Toml), cfg!(feature = "toml"));
+        assert_eq!(enabled.contains(&FileFormat::Ron), cfg!(feature = "ron"));
+        assert!(enabled.iter().all(FileFormat::is_enabled));
+    }
+
     #[test]
     fn test_file_new() {
         let path = "test.json".to_string();
@@ -201,7 +450,13 @@ mod test {
         std::fs::remove_file(path.clone()).unwrap();
 
         let file = File::from_path("test.json".to_string());
-        assert!(file.is_err());
+        assert!(matches!(
+            file,
+            Err(ConfigError::Io {
+                kind: std::io::ErrorKind::NotFound,
+                ..
+            })
+        ));
 
         let file = File::from_path("test_yaml.".to_string());
         assert!(file.is_err());
@@ -227,9 +482,154 @@ mod test {
         assert!(file.is_err());
     }
 
+    #[test]
+    #[cfg(all(feature = "read_file", feature = "json"))]
+    fn test_stream_records() {
+        let path = "test_stream.json".to_string();
+        let content =
+            r#"{"meta": {"skip": [1]}, "data": {"rows": [{"id": 1}, {"id": 2}, {"id": 3}]}}"#;
+        std::fs::write(&path, content).unwrap();
+        let mut ids = Vec::new();
+        let result = stream_records(&path, FileFormat::Json, "data.rows", |record| {
+            ids.push(record.get("id").cloned().unwrap());
+        });
+        let missing = stream_records(&path, FileFormat::Json, "data.cols", |_| {});
+        let not_array = stream_records(&path, FileFormat::Json, "meta", |_| {});
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(ids, vec![Value::Int(1), Value::Int(2), Value::Int(3)]);
+        assert_eq!(
+            missing.unwrap_err(),
+            ConfigError::MissingKey("data.cols".to_string())
+        );
+        assert_eq!(
+            not_array.unwrap_err(),
+            ConfigError::Convert {
+                key: "meta".to_string(),
+                error: crate::error::CannotConvert::new("Table", "Array"),
+            }
+        );
+    }
+
+    /// Streaming JSON and parsing other formats whole report the same errors.
+    #[test]
+    #[cfg(all(feature = "read_file", feature = "json", feature = "yaml"))]
+    fn test_stream_records_errors_match() {
+        let json = "test_stream_errors.json";
+        let yaml = "test_stream_errors.yaml";
+        std::fs::write(json, r#"{"name": "x", "meta": {"skip": [1]}}"#).unwrap();
+        std::fs::write(yaml, "name: x\nmeta:\n  skip: [1]\n").unwrap();
+        for key in ["name", "meta", "meta.skip.x", "name.x", "missing"] {
+            let from_json = stream_records(json, FileFormat::Json, key, |_| {});
+            let from_yaml = stream_records(yaml, FileFormat::Yaml, key, |_| {});
+            assert!(from_json.is_err());
+            assert_eq!(from_json, from_yaml, "{}", key);
+        }
+        std::fs::remove_file(json).unwrap();
+        std::fs::remove_file(yaml).unwrap();
+    }
+
+    #[test]
+    fn test_file_from_reader() {
+        let content = r#"{"key": "value"}"#;
+        let reader = std::io::Cursor::new(content.as_bytes().to_vec());
+        let file = File::from_reader("remote.json".to_string(), FileFormat::Json, reader).unwrap();
+        assert_eq!(file.path, "remote.json");
+        assert_eq!(file.format, FileFormat::Json);
+        assert_eq!(file.content, content);
+
+        let reader = std::io::Cursor::new(vec![0x7b, 0xff, 0x7d]);
+        let file = File::from_reader("remote.json".to_string(), FileFormat::Json, reader);
+        assert_eq!(
+            file.unwrap_err(),
+            ConfigError::NotUtf8 {
+                path: "remote.json".to_string()
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "read_file")]
+    fn test_file_from_path_not_utf8() {
+        let path = "test_not_utf8.json".to_string();
+        std::fs::write(&path, [0x7b, 0xff, 0xfe, 0x7d]).unwrap();
+        let file = File::from_path(path.clone());
+        let file_format = File::from_path_format(path.clone(), FileFormat::Json);
+        std::fs::remove_file(path.clone()).unwrap();
+        assert_eq!(
+            file.unwrap_err(),
+            ConfigError::NotUtf8 { path: path.clone() }
+        );
+        assert_eq!(file_format.unwrap_err(), ConfigError::NotUtf8 { path });
+    }
+
     mod formats {
         use super::*;
 
+        #[test]
+        fn test_parse_whitespace() {
+            let formats = [
+                #[cfg(feature = "ini")]
+                FileFormat::Ini,
+                #[cfg(feature = "yaml")]
+                FileFormat::Yaml,
+                #[cfg(feature = "toml")]
+                FileFormat::Toml,
+                #[cfg(feature = "ron")]
+                FileFormat::Ron,
+            ];
+            for format in formats {
+                let file = File::new_str("test", format, "   \n\t");
+                assert_eq!(file.parse(), Ok(Map::new()));
+            }
+        }
+
+        #[test]
+        #[cfg(feature = "json")]
+        fn test_parse_whitespace_json() {
+            let file = File::new_str("test", FileFormat::Json, "\u{feff}   \n\t");
+            assert_eq!(file.parse().unwrap_err(), ConfigError::Empty);
+            let options = ParseOptions {
+                json_allow_comments: true,
+                ..ParseOptions::default()
+            };
+            let file = File::new_str("test", FileFormat::Json, "\u{feff}");
+            assert_eq!(file.parse_with(&options), Ok(Map::new()));
+        }
+
+        #[test]
+        fn test_parse_comments_only() {
+            let files = [
+                #[cfg(feature = "ini")]
+                (FileFormat::Ini, "; nothing here\n# or here\n"),
+                #[cfg(feature = "yaml")]
+                (FileFormat::Yaml, "# nothing here\n"),
+                #[cfg(feature = "toml")]
+                (FileFormat::Toml, "\u{feff}# nothing here\n\n# or here\n"),
+                #[cfg(feature = "json")]
+                (FileFormat::Json, "// nothing here\n/* or here */"),
+            ];
+            let options = ParseOptions {
+                json_allow_comments: true,
+                ..ParseOptions::default()
+            };
+            for (format, content) in files {
+                let file = File::new_str("test", format, content);
+                assert_eq!(file.parse_with(&options), Ok(Map::new()));
+            }
+        }
+
+        #[test]
+        #[cfg(feature = "json")]
+        fn test_parse_bom() {
+            let file = File::new_str("test", FileFormat::Json, "\u{feff}{\"key\": 1}");
+            assert_eq!(
+                file.parse(),
+                Ok(Map::from_iter([("key".to_string(), Value::Int(1))]))
+            );
+        }
+
         #[test]
         #[cfg(feature = "ini")]
         fn test_parse_ini() {
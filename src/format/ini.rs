@@ -1,6 +1,10 @@
+use crate::file::{FileFormat, RepeatedKeyPolicy};
 use crate::value::{Map, Table, Value};
 
-pub(crate) fn deserialize(content: String) -> Result<Map<String, Value>, String> {
+pub(crate) fn deserialize(
+    content: String,
+    repeated_keys: RepeatedKeyPolicy,
+) -> Result<Map<String, Value>, String> {
     let mut map = Map::new();
     let ini = ini::Ini::load_from_str(&content).map_err(|e| e.to_string())?;
     for (sec, prop) in ini.iter() {
@@ -8,16 +12,13 @@ pub(crate) fn deserialize(content: String) -> Result<Map<String, Value>, String>
             Some(section) => {
                 let mut table = Table::new();
                 for (key, value) in prop.iter() {
-                    table.insert(
-                        key.to_string().to_string(),
-                        Value::String(value.to_string()),
-                    );
+                    insert_value(&mut table, key, value, repeated_keys);
                 }
                 map.insert(section.to_string(), Value::Table(table));
             }
             None => {
                 for (key, value) in prop.iter() {
-                    map.insert(key.to_string(), Value::String(value.to_string()));
+                    insert_value(&mut map, key, value, repeated_keys);
                 }
             }
         }
@@ -25,6 +26,71 @@ pub(crate) fn deserialize(content: String) -> Result<Map<String, Value>, String>
     Ok(map)
 }
 
+/// Writes top-level scalars to the global section and tables as `[section]` blocks. Arrays
+/// of scalars are written as repeated keys and `null` as an empty value.
+pub(crate) fn serialize(value: Map<String, Value>) -> Result<String, String> {
+    let value = Value::Table(value);
+    value
+        .check_representable(FileFormat::Ini)
+        .map_err(|e| format!("Failed to serialize INI: {}", e))?;
+    let Value::Table(map) = value else {
+        unreachable!()
+    };
+
+    let mut ini = ini::Ini::new();
+    let mut sections = Vec::new();
+    for (key, value) in map {
+        match value {
+            Value::Table(table) => sections.push((key, table)),
+            value => append_value(ini.general_section_mut(), key, value),
+        }
+    }
+    for (name, table) in sections {
+        let mut properties = ini::Properties::new();
+        for (key, value) in table {
+            append_value(&mut properties, key, value);
+        }
+        ini.entry(Some(name)).or_insert(properties);
+    }
+
+    let mut out = Vec::new();
+    ini.write_to(&mut out)
+        .map_err(|e| format!("Failed to serialize INI: {}", e))?;
+    String::from_utf8(out).map_err(|e| format!("Failed to serialize INI: {}", e))
+}
+
+fn append_value(properties: &mut ini::Properties, key: String, value: Value) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                append_value(properties, key.clone(), item);
+            }
+        }
+        Value::None => properties.append(key, ""),
+        Value::String(s) => properties.append(key, s),
+        value => properties.append(
+            key,
+            value
+                .as_str()
+                .map_or_else(|| value.to_string(), str::to_string),
+        ),
+    }
+}
+
+fn insert_value(table: &mut Table, key: &str, value: &str, repeated_keys: RepeatedKeyPolicy) {
+    let value = Value::String(value.to_string());
+    match (repeated_keys, table.get_mut(key)) {
+        (RepeatedKeyPolicy::Array, Some(Value::Array(values))) => values.push(value),
+        (RepeatedKeyPolicy::Array, Some(existing)) => {
+            let first = std::mem::take(existing);
+            *existing = Value::Array(vec![first, value]);
+        }
+        _ => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -33,7 +99,7 @@ mod test {
     #[test]
     fn test_invalid() {
         let ini_content = r#"[section"#;
-        let result = deserialize(ini_content.to_string());
+        let result = deserialize(ini_content.to_string(), RepeatedKeyPolicy::Last);
         assert!(result.is_err());
     }
 
@@ -43,7 +109,7 @@ mod test {
 key1 = "value1"
 key2 = "value2"
 "#;
-        let parsed_map = deserialize(ini_content.to_string()).unwrap();
+        let parsed_map = deserialize(ini_content.to_string(), RepeatedKeyPolicy::Last).unwrap();
 
         assert_eq!(
             parsed_map,
@@ -54,13 +120,100 @@ key2 = "value2"
         );
     }
 
+    #[test]
+    fn test_repeated_keys() {
+        let ini_content = r#"
+key = a
+key = b
+"#;
+        let parsed_map = deserialize(ini_content.to_string(), RepeatedKeyPolicy::Last).unwrap();
+        assert_eq!(
+            parsed_map.get("key").unwrap(),
+            &Value::String("b".to_string())
+        );
+        let parsed_map = deserialize(ini_content.to_string(), RepeatedKeyPolicy::Array).unwrap();
+        assert_eq!(
+            parsed_map.get("key").unwrap(),
+            &Value::Array(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let map = Map::from_iter([
+            (
+                "server".to_string(),
+                Value::Table(Table::from_iter([
+                    ("host".to_string(), Value::from("localhost")),
+                    ("port".to_string(), Value::Int(80)),
+                    (
+                        "alias".to_string(),
+                        Value::Array(vec![Value::from("a"), Value::from("b")]),
+                    ),
+                ])),
+            ),
+            ("debug".to_string(), Value::Bool(true)),
+            ("ratio".to_string(), Value::Float(0.5)),
+            ("empty".to_string(), Value::None),
+        ]);
+        let ini = serialize(map).unwrap();
+        assert_eq!(
+            ini,
+            "debug=true\nratio=0.5\nempty=\n\n[server]\nhost=localhost\nport=80\nalias=a\nalias=b\n"
+        );
+
+        let parsed = deserialize(ini, RepeatedKeyPolicy::Array).unwrap();
+        assert_eq!(parsed.get("debug").unwrap(), &Value::from("true"));
+        assert_eq!(
+            parsed.get("server").unwrap().get("alias").unwrap(),
+            &Value::Array(vec![Value::from("a"), Value::from("b")])
+        );
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let ini_content = "name=app\n\n[db]\nurl=postgres://localhost\nuser=admin\n";
+        let parsed = deserialize(ini_content.to_string(), RepeatedKeyPolicy::Last).unwrap();
+        assert_eq!(serialize(parsed.clone()).unwrap(), ini_content);
+        let reparsed =
+            deserialize(serialize(parsed.clone()).unwrap(), RepeatedKeyPolicy::Last).unwrap();
+        assert_eq!(reparsed, parsed);
+    }
+
+    #[test]
+    fn test_serialize_too_deep() {
+        let map = Map::from_iter([(
+            "server".to_string(),
+            Value::Table(Table::from_iter([(
+                "tls".to_string(),
+                Value::Table(Table::from_iter([("cert".to_string(), Value::from("a"))])),
+            )])),
+        )]);
+        assert_eq!(
+            serialize(map).unwrap_err(),
+            "Failed to serialize INI: server.tls: INI does not support nesting beyond one level of sections"
+        );
+
+        let map = Map::from_iter([(
+            "list".to_string(),
+            Value::Array(vec![Value::Array(vec![Value::Int(1)])]),
+        )]);
+        assert_eq!(
+            serialize(map).unwrap_err(),
+            "Failed to serialize INI: list[0]: INI does not support nested arrays"
+        );
+    }
+
     #[test]
     fn test_deserialize() {
         let ini_content = r#"
 [section]
 key = "value"
 "#;
-        let parsed_map = deserialize(ini_content.to_string()).unwrap();
+        let parsed_map = deserialize(ini_content.to_string(), RepeatedKeyPolicy::Last).unwrap();
         assert_eq!(
             parsed_map,
             Map::from_iter(vec![(
@@ -1,71 +1,270 @@
+#[cfg(feature = "read_file")]
+use crate::value::ValueKind;
 use crate::value::{Map, Table, Value};
 
 pub(crate) fn deserialize(content: String) -> Result<Map<String, Value>, String> {
-    let json_content: serde_json::Value =
-        serde_json::from_str(&content).map_err(|e| format!("Failed to parse JSON: {}", e))?;
-    let mut map = Map::new();
-    if let Some(obj) = json_content.as_object() {
-        for (key, value) in obj {
-            map.insert(key.clone(), from_json_value(value));
+    let mut deserializer = serde_json::Deserializer::from_str(&content);
+    let json_content = serde::Deserialize::deserialize(&mut deserializer)
+        .and_then(|value: serde_json::Value| deserializer.end().map(|_| value))
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    match json_content {
+        serde_json::Value::Object(obj) => Ok(obj
+            .into_iter()
+            .map(|(key, value)| (key, from_json_value(value)))
+            .collect()),
+        other => Err(format!(
+            "JSON root must be an object, found {}",
+            from_json_value(other).kind()
+        )),
+    }
+}
+
+/// Reads the array at `path` from `reader` one element at a time, calling `f` for each
+/// element without keeping the whole array in memory. Returns the kind of the value found at
+/// `path`, `f` is only called when it is an array.
+#[cfg(feature = "read_file")]
+pub(crate) fn stream_array<R: std::io::Read>(
+    reader: R,
+    path: &[&str],
+    f: &mut impl FnMut(Value),
+) -> Result<Option<ValueKind>, String> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    serde::de::DeserializeSeed::deserialize(StreamSeed { path, f }, &mut deserializer)
+        .and_then(|found| deserializer.end().map(|_| found))
+        .map_err(|e| format!("Failed to parse JSON: {}", e))
+}
+
+/// Walks down `path` through tables, then feeds the array found there to `f`.
+/// Deserializes to the kind of the value at the path, or `None` when it is missing.
+#[cfg(feature = "read_file")]
+struct StreamSeed<'a, F> {
+    path: &'a [&'a str],
+    f: &'a mut F,
+}
+
+#[cfg(feature = "read_file")]
+impl<F> StreamSeed<'_, F> {
+    /// Scalars are only found at the end of the path, anything deeper is missing.
+    fn leaf(&self, kind: ValueKind) -> Option<ValueKind> {
+        self.path.is_empty().then_some(kind)
+    }
+}
+
+#[cfg(feature = "read_file")]
+impl<'de, F: FnMut(Value)> serde::de::DeserializeSeed<'de> for StreamSeed<'_, F> {
+    type Value = Option<ValueKind>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+#[cfg(feature = "read_file")]
+impl<'de, F: FnMut(Value)> serde::de::Visitor<'de> for StreamSeed<'_, F> {
+    type Value = Option<ValueKind>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "any JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(self.leaf(ValueKind::None))
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(self.leaf(ValueKind::Bool))
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(self.leaf(ValueKind::Int))
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(self.leaf(ValueKind::Int))
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(self.leaf(ValueKind::Float))
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> {
+        Ok(self.leaf(ValueKind::String))
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut found = self.leaf(ValueKind::Table);
+        let Some((key, rest)) = self.path.split_first() else {
+            while map
+                .next_entry::<serde::de::IgnoredAny, serde::de::IgnoredAny>()?
+                .is_some()
+            {}
+            return Ok(found);
+        };
+        while let Some(next) = map.next_key::<String>()? {
+            if found.is_none() && next == *key {
+                found = map.next_value_seed(StreamSeed {
+                    path: rest,
+                    f: &mut *self.f,
+                })?;
+            } else {
+                map.next_value::<serde::de::IgnoredAny>()?;
+            }
+        }
+        Ok(found)
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        if !self.path.is_empty() {
+            while seq.next_element::<serde::de::IgnoredAny>()?.is_some() {}
+            return Ok(None);
+        }
+        while let Some(element) = seq.next_element::<serde_json::Value>()? {
+            (self.f)(from_json_value(element));
+        }
+        Ok(Some(ValueKind::Array))
+    }
+}
+
+/// Replaces `//` and `/* */` comments outside of strings with spaces, keeping newlines so
+/// parse errors still point at the right line.
+pub(crate) fn strip_comments(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                out.push(' ');
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push(c);
+                        break;
+                    }
+                    out.push(' ');
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                out.push_str("  ");
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    out.push(if c == '\n' { c } else { ' ' });
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            _ => out.push(c),
         }
     }
-    Ok(map)
+    out
 }
 
-fn from_json_value(value: &serde_json::Value) -> Value {
+fn from_json_value(value: serde_json::Value) -> Value {
     match value {
         serde_json::Value::Null => Value::None,
-        serde_json::Value::Bool(b) => Value::Bool(*b),
+        serde_json::Value::Bool(b) => Value::Bool(b),
         serde_json::Value::Number(n) => {
             if let Some(i) = n.as_i64() {
                 Value::Int(i)
+            } else if let Some(u) = n.as_u64() {
+                Value::from(u)
             } else {
                 Value::Float(n.as_f64().unwrap_or(0.0))
             }
         }
-        serde_json::Value::String(s) => Value::String(s.clone()),
+        serde_json::Value::String(s) => Value::String(s),
         serde_json::Value::Array(arr) => {
-            let mut values = Vec::new();
-            for item in arr {
-                values.push(from_json_value(item));
-            }
-            Value::Array(values)
-        }
-        serde_json::Value::Object(obj) => {
-            let mut table = Table::new();
-            for (key, value) in obj {
-                table.insert(key.clone(), from_json_value(value));
-            }
-            Value::Table(table)
+            Value::Array(arr.into_iter().map(from_json_value).collect())
         }
+        serde_json::Value::Object(obj) => Value::Table(
+            obj.into_iter()
+                .map(|(key, value)| (key, from_json_value(value)))
+                .collect::<Table>(),
+        ),
     }
 }
 
-pub(crate) fn serialize(value: Map<String, Value>) -> String {
-    let json_value = to_json_value(value);
-    serde_json::to_string(&json_value).unwrap()
+pub(crate) fn serialize(value: Map<String, Value>) -> Result<String, String> {
+    let json_value = to_json_value(value)?;
+    Ok(serde_json::to_string(&json_value).unwrap())
 }
 
-fn to_json_value(value: Map<String, Value>) -> serde_json::Value {
-    serde_json::Value::Object(
+fn to_json_value(value: Map<String, Value>) -> Result<serde_json::Value, String> {
+    Ok(serde_json::Value::Object(
         value
             .into_iter()
-            .map(|(k, v)| (k, to_json_value_single(v)))
-            .collect(),
-    )
+            .map(|(k, v)| Ok((k, to_json_value_single(v)?)))
+            .collect::<Result<_, String>>()?,
+    ))
 }
 
-fn to_json_value_single(value: Value) -> serde_json::Value {
-    match value {
+fn to_json_value_single(value: Value) -> Result<serde_json::Value, String> {
+    Ok(match value {
         Value::None => serde_json::Value::Null,
         Value::Bool(b) => serde_json::Value::Bool(b),
         Value::Int(i) => serde_json::Value::Number(serde_json::Number::from(i)),
-        Value::Float(f) => serde_json::Value::Number(serde_json::Number::from_f64(f).unwrap()),
+        Value::UInt(u) => serde_json::Value::Number(serde_json::Number::from(u)),
+        #[cfg(feature = "i128")]
+        Value::BigInt(i) => return Err(format!("JSON does not support integer {}", i)),
+        Value::Float(f) => serde_json::Number::from_f64(f)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| format!("JSON does not support float {}", f))?,
         Value::String(s) => serde_json::Value::String(s),
-        Value::Array(arr) => {
-            serde_json::Value::Array(arr.into_iter().map(to_json_value_single).collect())
+        #[cfg(feature = "intern")]
+        Value::Shared(s) => serde_json::Value::String(s.to_string()),
+        Value::Datetime(dt) => serde_json::Value::String(dt),
+        Value::Array(arr) => serde_json::Value::Array(
+            arr.into_iter()
+                .map(to_json_value_single)
+                .collect::<Result<_, String>>()?,
+        ),
+        Value::Table(table) => to_json_value(table)?,
+    })
+}
+
+/// Lossy conversion for interop: non-finite floats become `null`, and integers beyond the
+/// JSON number range become strings. Saving as JSON reports these as errors instead.
+impl From<Value> for serde_json::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::None => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(b),
+            Value::Int(i) => serde_json::Value::from(i),
+            Value::UInt(u) => serde_json::Value::from(u),
+            #[cfg(feature = "i128")]
+            Value::BigInt(i) => serde_json::Number::from_i128(i)
+                .map(serde_json::Value::Number)
+                .unwrap_or_else(|| serde_json::Value::String(i.to_string())),
+            Value::Float(f) => serde_json::Value::from(f),
+            Value::String(s) => serde_json::Value::String(s),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => serde_json::Value::String(s.to_string()),
+            Value::Datetime(dt) => serde_json::Value::String(dt),
+            Value::Array(arr) => {
+                serde_json::Value::Array(arr.into_iter().map(Into::into).collect())
+            }
+            Value::Table(table) => {
+                serde_json::Value::Object(table.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
         }
-        Value::Table(table) => to_json_value(table),
     }
 }
 
@@ -74,6 +273,29 @@ mod test {
     use super::*;
     use crate::value::Value;
 
+    #[test]
+    fn test_strip_comments() {
+        let content = r#"{
+            // line comment
+            "url": "http://example.com/*x*/", /* block
+            comment */ "escaped": "a\"//b",
+            "key": 1 // trailing
+        }"#;
+        let stripped = strip_comments(content);
+        assert_eq!(stripped.len(), content.len());
+        assert_eq!(stripped.lines().count(), content.lines().count());
+        let map = deserialize(stripped).unwrap();
+        assert_eq!(
+            map.get("url"),
+            Some(&Value::String("http://example.com/*x*/".to_string()))
+        );
+        assert_eq!(
+            map.get("escaped"),
+            Some(&Value::String("a\"//b".to_string()))
+        );
+        assert_eq!(map.get("key"), Some(&Value::Int(1)));
+    }
+
     #[test]
     fn test_invalid() {
         let json_string = r#"{"key": "value""#;
@@ -84,16 +306,19 @@ mod test {
     #[test]
     fn test_non_object_json() {
         let test_cases = vec![
-            "42",        // Number
-            "true",      // Boolean
-            "\"hello\"", // String
-            "null",      // Null
-            "[]",        // Array
+            ("42", "Int"),
+            ("true", "Bool"),
+            ("\"hello\"", "String"),
+            ("null", "None"),
+            ("[1, 2, 3]", "Array"),
         ];
 
-        for case in test_cases {
-            let parsed_map = deserialize(case.to_string()).unwrap();
-            assert!(parsed_map.is_empty());
+        for (case, kind) in test_cases {
+            let result = deserialize(case.to_string());
+            assert_eq!(
+                result.unwrap_err(),
+                format!("JSON root must be an object, found {}", kind)
+            );
         }
     }
 
@@ -127,7 +352,7 @@ mod test {
     fn test_serialize() {
         let mut map = Map::new();
         map.insert("key".to_string(), Value::String("value".to_string()));
-        let json_string = serialize(map);
+        let json_string = serialize(map).unwrap();
         assert_eq!(json_string, r#"{"key":"value"}"#);
     }
 
@@ -138,45 +363,91 @@ mod test {
             "array".to_string(),
             Value::Array(vec![Value::Int(1), Value::String("two".to_string())]),
         );
-        let json_string = serialize(map);
+        let json_string = serialize(map).unwrap();
         assert_eq!(json_string, r#"{"array":[1,"two"]}"#);
     }
 
+    #[test]
+    fn test_serialize_non_finite_float() {
+        let map = Map::from_iter([("ratio".to_string(), Value::Float(f64::NAN))]);
+        assert_eq!(
+            serialize(map).unwrap_err(),
+            "JSON does not support float NaN"
+        );
+        let map = Map::from_iter([(
+            "list".to_string(),
+            Value::Array(vec![Value::Float(1.5), Value::Float(f64::INFINITY)]),
+        )]);
+        assert_eq!(
+            serialize(map).unwrap_err(),
+            "JSON does not support float inf"
+        );
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let result = deserialize(r#"{"a":1} garbage"#.to_string());
+        assert_eq!(
+            result.unwrap_err(),
+            "Failed to parse JSON: trailing characters at line 1 column 9"
+        );
+        let result = deserialize("{\"a\":1}\n{\"b\":2}".to_string());
+        assert!(result.unwrap_err().contains("trailing characters"));
+        assert!(deserialize("{\"a\":1}\n  \n".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_value_into_json_value() {
+        let value = Value::Table(Table::from_iter([
+            ("int".to_string(), Value::Int(1)),
+            ("nan".to_string(), Value::Float(f64::NAN)),
+            (
+                "list".to_string(),
+                Value::Array(vec![Value::None, Value::Bool(true), Value::from("a")]),
+            ),
+        ]));
+        let json_value: serde_json::Value = value.into();
+        assert_eq!(
+            json_value,
+            serde_json::json!({"int": 1, "nan": null, "list": [null, true, "a"]})
+        );
+    }
+
     mod from_json_value {
         use super::*;
 
         #[test]
         fn test_from_null() {
             let json_value = serde_json::Value::Null;
-            let parsed_value = from_json_value(&json_value);
+            let parsed_value = from_json_value(json_value);
             assert_eq!(parsed_value, Value::None);
         }
 
         #[test]
         fn test_from_bool() {
             let json_value = serde_json::Value::Bool(true);
-            let parsed_value = from_json_value(&json_value);
+            let parsed_value = from_json_value(json_value);
             assert_eq!(parsed_value, Value::Bool(true));
         }
 
         #[test]
         fn test_from_int() {
             let json_value = serde_json::Value::Number(serde_json::Number::from(42));
-            let parsed_value = from_json_value(&json_value);
+            let parsed_value = from_json_value(json_value);
             assert_eq!(parsed_value, Value::Int(42));
         }
 
         #[test]
         fn test_from_float() {
             let json_value = serde_json::Value::Number(serde_json::Number::from_f64(3.1).unwrap());
-            let parsed_value = from_json_value(&json_value);
+            let parsed_value = from_json_value(json_value);
             assert_eq!(parsed_value, Value::Float(3.1));
         }
 
         #[test]
         fn test_from_string() {
             let json_value = serde_json::Value::String("Hello".to_string());
-            let parsed_value = from_json_value(&json_value);
+            let parsed_value = from_json_value(json_value);
             assert_eq!(parsed_value, Value::String("Hello".to_string()));
         }
 
@@ -186,7 +457,7 @@ mod test {
                 serde_json::Value::Number(serde_json::Number::from(1)),
                 serde_json::Value::String("two".to_string()),
             ]);
-            let parsed_value = from_json_value(&json_value);
+            let parsed_value = from_json_value(json_value);
             assert_eq!(
                 parsed_value,
                 Value::Array(vec![Value::Int(1), Value::String("two".to_string())])
@@ -204,7 +475,7 @@ mod test {
                 .cloned()
                 .collect(),
             );
-            let parsed_value = from_json_value(&json_value);
+            let parsed_value = from_json_value(json_value);
             assert_eq!(
                 parsed_value,
                 Value::Table(Table::from_iter(vec![(
@@ -221,21 +492,21 @@ mod test {
         #[test]
         fn test_none_to_json_value_single() {
             let value = Value::None;
-            let json_value = to_json_value_single(value);
+            let json_value = to_json_value_single(value).unwrap();
             assert_eq!(json_value, serde_json::Value::Null);
         }
 
         #[test]
         fn test_bool_to_json_value_single() {
             let value = Value::Bool(true);
-            let json_value = to_json_value_single(value);
+            let json_value = to_json_value_single(value).unwrap();
             assert_eq!(json_value, serde_json::Value::Bool(true));
         }
 
         #[test]
         fn test_int_to_json_value_single() {
             let value = Value::Int(42);
-            let json_value = to_json_value_single(value);
+            let json_value = to_json_value_single(value).unwrap();
             assert_eq!(
                 json_value,
                 serde_json::Value::Number(serde_json::Number::from(42))
@@ -245,7 +516,7 @@ mod test {
         #[test]
         fn test_float_to_json_value_single() {
             let value = Value::Float(3.1);
-            let json_value = to_json_value_single(value);
+            let json_value = to_json_value_single(value).unwrap();
             assert_eq!(
                 json_value,
                 serde_json::Value::Number(serde_json::Number::from_f64(3.1).unwrap())
@@ -255,14 +526,14 @@ mod test {
         #[test]
         fn test_string_to_json_value_single() {
             let value = Value::String("Hello".to_string());
-            let json_value = to_json_value_single(value);
+            let json_value = to_json_value_single(value).unwrap();
             assert_eq!(json_value, serde_json::Value::String("Hello".to_string()));
         }
 
         #[test]
         fn test_array_to_json_value_single() {
             let value = Value::Array(vec![Value::Int(1), Value::String("two".to_string())]);
-            let json_value = to_json_value_single(value);
+            let json_value = to_json_value_single(value).unwrap();
             assert_eq!(
                 json_value,
                 serde_json::Value::Array(vec![
@@ -277,7 +548,7 @@ mod test {
             let mut table = Table::new();
             table.insert("key".to_string(), Value::String("value".to_string()));
             let value = Value::Table(table);
-            let json_value = to_json_value_single(value);
+            let json_value = to_json_value_single(value).unwrap();
             assert_eq!(
                 json_value,
                 serde_json::Value::Object(
@@ -296,7 +567,7 @@ mod test {
         fn test_to_json_value() {
             let mut map = Map::new();
             map.insert("key".to_string(), Value::String("value".to_string()));
-            let json_value = to_json_value(map);
+            let json_value = to_json_value(map).unwrap();
             assert_eq!(
                 json_value,
                 serde_json::Value::Object(
@@ -1,59 +1,74 @@
 use crate::value::{Map, Value};
 
 pub(crate) fn deserialize(content: String) -> Result<Map<String, Value>, String> {
-    let parsed_value: ron::Value = ron::from_str(&content).map_err(|e| e.to_string())?;
+    let parsed_value: ron::Value =
+        ron::from_str(&content).map_err(|e| format!("Failed to parse RON: {}", e))?;
     let mut map = Map::new();
     match parsed_value {
         ron::Value::Map(m) => {
             for (key, value) in m {
-                map.insert(check_key(key), from_ron_value(value));
+                map.insert(check_key(key)?, from_ron_value(value)?);
             }
         }
-        _ => panic!("Expected a RON map"),
+        _ => return Err("RON root must be a map".to_string()),
     }
     Ok(map)
 }
 
-fn from_ron_value(value: ron::Value) -> Value {
-    match value {
+fn from_ron_value(value: ron::Value) -> Result<Value, String> {
+    Ok(match value {
         ron::Value::Char(c) => Value::String(c.to_string()),
         ron::Value::String(s) => Value::String(s),
         ron::Value::Bytes(b) => Value::String(String::from_utf8_lossy(&b).to_string()),
-        ron::Value::Number(n) => {
-            let float = n.into_f64();
-            if float.fract() == 0.0 {
-                Value::Int(float as i64)
-            } else {
-                Value::Float(float)
-            }
-        }
+        ron::Value::Number(n) => from_ron_number(n),
         ron::Value::Option(o) => match o {
-            Some(v) => from_ron_value(*v),
+            Some(v) => from_ron_value(*v)?,
             None => Value::None,
         },
         ron::Value::Bool(b) => Value::Bool(b),
         ron::Value::Seq(s) => {
             let mut values = Vec::new();
             for item in s {
-                values.push(from_ron_value(item));
+                values.push(from_ron_value(item)?);
             }
             Value::Array(values)
         }
         ron::Value::Map(map) => {
             let mut new_map = Map::new();
             for (key, value) in map {
-                new_map.insert(check_key(key), from_ron_value(value));
+                new_map.insert(check_key(key)?, from_ron_value(value)?);
             }
             Value::Table(new_map)
         }
         ron::Value::Unit => Value::None,
+    })
+}
+
+/// Keeps the integer/float distinction RON makes in its source, so `1.0` stays a float.
+fn from_ron_number(number: ron::Number) -> Value {
+    match number {
+        ron::Number::I8(i) => Value::Int(i.into()),
+        ron::Number::I16(i) => Value::Int(i.into()),
+        ron::Number::I32(i) => Value::Int(i.into()),
+        ron::Number::I64(i) => Value::Int(i),
+        ron::Number::U8(i) => Value::Int(i.into()),
+        ron::Number::U16(i) => Value::Int(i.into()),
+        ron::Number::U32(i) => Value::Int(i.into()),
+        ron::Number::U64(i) => Value::from(i),
+        number @ (ron::Number::F32(_) | ron::Number::F64(_)) => Value::Float(number.into_f64()),
+        #[cfg(feature = "i128")]
+        ron::Number::I128(i) => Value::from(i),
+        #[cfg(feature = "i128")]
+        ron::Number::U128(i) => Value::from(i),
+        #[allow(unreachable_patterns)]
+        number => Value::Int(number.into_f64() as i64),
     }
 }
 
-fn check_key(key: ron::Value) -> String {
+fn check_key(key: ron::Value) -> Result<String, String> {
     match key {
-        ron::Value::String(s) => s,
-        _ => panic!("Invalid key type in RON map"),
+        ron::Value::String(s) => Ok(s),
+        _ => Err("RON keys must be strings".to_string()),
     }
 }
 
@@ -67,7 +82,11 @@ pub(crate) fn serialize(value: Map<String, Value>) -> String {
 
 fn to_ron_value(value: Value) -> ron::Value {
     match value {
+        Value::None => ron::Value::Option(None),
         Value::String(s) => ron::Value::String(s),
+        #[cfg(feature = "intern")]
+        Value::Shared(s) => ron::Value::String(s.to_string()),
+        Value::Datetime(dt) => ron::Value::String(dt),
         Value::Int(i) => {
             if let Ok(i32_value) = i.try_into() {
                 ron::Value::Number(ron::Number::I32(i32_value))
@@ -75,6 +94,9 @@ fn to_ron_value(value: Value) -> ron::Value {
                 ron::Value::Number(ron::Number::I64(i))
             }
         }
+        Value::UInt(u) => ron::Value::Number(ron::Number::U64(u)),
+        #[cfg(feature = "i128")]
+        Value::BigInt(i) => ron::Value::Number(ron::Number::I128(i)),
         Value::Float(f) => ron::Value::Number(ron::Number::from(f)),
         Value::Bool(b) => ron::Value::Bool(b),
         Value::Array(arr) => {
@@ -91,7 +113,6 @@ fn to_ron_value(value: Value) -> ron::Value {
             }
             ron::Value::Map(ron_map)
         }
-        _ => panic!("Unsupported value type for RON serialization"),
     }
 }
 
@@ -104,14 +125,15 @@ mod test {
     fn test_check_key() {
         let key = ron::Value::String("key".to_string());
         let result = check_key(key);
-        assert_eq!(result, "key");
+        assert_eq!(result, Ok("key".to_string()));
     }
 
     #[test]
-    #[should_panic]
-    fn test_check_key_not_string() {
-        let key = ron::Value::Number(ron::Number::from(42));
-        let _result = check_key(key);
+    fn test_non_string_keys() {
+        let result = deserialize("{1: 2}".to_string());
+        assert_eq!(result.unwrap_err(), "RON keys must be strings");
+        let result = deserialize("(a: {1: 2})".to_string());
+        assert_eq!(result.unwrap_err(), "RON keys must be strings");
     }
 
     #[test]
@@ -122,10 +144,22 @@ mod test {
     }
 
     #[test]
-    #[should_panic]
+    fn test_trailing_data() {
+        let result = deserialize("(a: 1) (b: 2)".to_string());
+        assert_eq!(
+            result.unwrap_err(),
+            "Failed to parse RON: 1:8: Non-whitespace trailing characters"
+        );
+        assert!(deserialize("(a: 1) garbage".to_string()).is_err());
+        assert!(deserialize("(a: 1)\n\n".to_string()).is_ok());
+    }
+
+    #[test]
     fn test_expected_ron_map() {
-        let non_map_ron = r#""string_value""#; // Not a map, should panic
-        let _result = deserialize(non_map_ron.to_string());
+        for content in [r#""string_value""#, "[1, 2]"] {
+            let result = deserialize(content.to_string());
+            assert_eq!(result.unwrap_err(), "RON root must be a map");
+        }
     }
 
     #[test]
@@ -159,48 +193,83 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_round_trip_none() {
+        let map = Map::from_iter(vec![
+            ("key".to_string(), Value::None),
+            (
+                "table".to_string(),
+                Value::Table(Map::from_iter(vec![("nested".to_string(), Value::None)])),
+            ),
+        ]);
+        let serialized = serialize(map.clone());
+        let parsed_map = deserialize(serialized).unwrap();
+        assert_eq!(parsed_map, map);
+    }
+
+    #[test]
+    fn test_round_trip_whole_float() {
+        let parsed_map = deserialize("(x: 2.0, y: 2)".to_string()).unwrap();
+        assert_eq!(parsed_map.get("x"), Some(&Value::Float(2.0)));
+        assert_eq!(parsed_map.get("y"), Some(&Value::Int(2)));
+
+        let serialized = serialize(parsed_map.clone());
+        assert!(serialized.contains("\"x\":2.0"), "{}", serialized);
+        assert_eq!(deserialize(serialized).unwrap(), parsed_map);
+    }
+
     mod from_ron_value {
         use super::*;
 
         #[test]
         fn test_from_null() {
             let ron_value = ron::Value::Unit;
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(value, Value::None);
         }
 
         #[test]
         fn test_from_char() {
             let ron_value = ron::Value::Char('c');
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(value, Value::String("c".to_string()));
         }
 
         #[test]
         fn test_from_string() {
             let ron_value = ron::Value::String("value".to_string());
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(value, Value::String("value".to_string()));
         }
 
         #[test]
         fn test_from_int() {
             let ron_value = ron::Value::Number(ron::Number::from(42));
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(value, Value::Int(42));
         }
 
         #[test]
         fn test_from_float() {
             let ron_value = ron::Value::Number(ron::Number::from(3.1));
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(value, Value::Float(3.1));
         }
 
+        #[test]
+        fn test_from_whole_float() {
+            let ron_value = ron::Value::Number(ron::Number::from(1.0));
+            let value = from_ron_value(ron_value).unwrap();
+            assert_eq!(value, Value::Float(1.0));
+            let ron_value = ron::Value::Number(ron::Number::U64(u64::MAX));
+            let value = from_ron_value(ron_value).unwrap();
+            assert_eq!(value, Value::from(u64::MAX));
+        }
+
         #[test]
         fn test_from_bool() {
             let ron_value = ron::Value::Bool(true);
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(value, Value::Bool(true));
         }
 
@@ -210,7 +279,7 @@ mod test {
                 ron::Value::Number(ron::Number::from(1)),
                 ron::Value::String("two".to_string()),
             ]);
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(
                 value,
                 Value::Array(vec![Value::Int(1), Value::String("two".to_string())])
@@ -220,7 +289,7 @@ mod test {
         #[test]
         fn test_from_bytes() {
             let ron_value = ron::Value::Bytes(vec![1, 2, 3]);
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(value, Value::String("\u{1}\u{2}\u{3}".to_string()));
         }
 
@@ -230,7 +299,7 @@ mod test {
                 "key".to_string(),
                 ron::Value::String("value".to_string()),
             )]));
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(
                 value,
                 Value::Table(Map::from_iter(vec![(
@@ -244,14 +313,14 @@ mod test {
         fn test_from_option() {
             let ron_value =
                 ron::Value::Option(Some(Box::new(ron::Value::String("value".to_string()))));
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(value, Value::String("value".to_string()));
         }
 
         #[test]
         fn test_from_option_none() {
             let ron_value = ron::Value::Option(None);
-            let value = from_ron_value(ron_value);
+            let value = from_ron_value(ron_value).unwrap();
             assert_eq!(value, Value::None);
         }
     }
@@ -316,12 +385,10 @@ mod test {
         }
 
         #[test]
-        fn test_unsupported_value() {
+        fn test_none_to_ron_value() {
             let value = Value::None;
-            let result = std::panic::catch_unwind(|| {
-                to_ron_value(value);
-            });
-            assert!(result.is_err());
+            let ron_value = to_ron_value(value);
+            assert_eq!(ron_value, ron::Value::Option(None));
         }
     }
 }
@@ -15,7 +15,7 @@ fn from_toml_value(value: &toml::Value) -> Value {
         toml::Value::Integer(i) => Value::Int(*i),
         toml::Value::Float(f) => Value::Float(*f),
         toml::Value::Boolean(b) => Value::Bool(*b),
-        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Datetime(dt) => Value::Datetime(dt.to_string()),
         toml::Value::Array(arr) => {
             let mut values = Vec::new();
             for item in arr {
@@ -33,42 +33,178 @@ fn from_toml_value(value: &toml::Value) -> Value {
     }
 }
 
-pub(crate) fn serialize(value: Map<String, Value>) -> String {
+/// Collects the comment lines right above each key, keyed by its dotted path.
+/// Content that doesn't parse has no descriptions, `deserialize` reports the error.
+pub(crate) fn descriptions(content: &str) -> Map<String, String> {
+    let mut descriptions = Map::new();
+    if let Ok(document) = content.parse::<toml_edit::DocumentMut>() {
+        collect_descriptions(document.as_table(), "", &mut descriptions);
+    }
+    descriptions
+}
+
+fn collect_descriptions(table: &toml_edit::Table, prefix: &str, out: &mut Map<String, String>) {
+    for (key, item) in table.iter() {
+        let path = format!("{}{}", prefix, key);
+        let decor = match item {
+            toml_edit::Item::Table(inner) => inner.decor(),
+            _ => match table.key(key) {
+                Some(key) => key.leaf_decor(),
+                None => continue,
+            },
+        };
+        if let Some(description) = decor
+            .prefix()
+            .and_then(|raw| raw.as_str())
+            .and_then(leading_comment)
+        {
+            out.insert(path.clone(), description);
+        }
+        if let toml_edit::Item::Table(inner) = item {
+            collect_descriptions(inner, &format!("{}.", path), out);
+        }
+    }
+}
+
+/// Joins the comment lines directly above a key, stopping at the first blank line.
+fn leading_comment(prefix: &str) -> Option<String> {
+    let mut lines: Vec<&str> = prefix
+        .trim_end_matches([' ', '\t'])
+        .lines()
+        .rev()
+        .map(str::trim)
+        .take_while(|line| line.starts_with('#'))
+        .map(|line| line.trim_start_matches('#').trim())
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+/// The text of the number under `keys` exactly as written, like `1.10` or `0x1F`.
+pub(crate) fn raw_scalar(content: &str, keys: &[String]) -> Option<String> {
+    let document = content.parse::<toml_edit::DocumentMut>().ok()?;
+    let item = keys
+        .iter()
+        .try_fold(document.as_item(), |item, key| item.get(key.as_str()))?;
+    let repr = match item.as_value()? {
+        toml_edit::Value::Integer(i) => i.as_repr()?,
+        toml_edit::Value::Float(f) => f.as_repr()?,
+        _ => return None,
+    };
+    repr.as_raw().as_str().map(str::to_string)
+}
+
+/// Tables with at most this many keys are kept inline when inline tables are enabled
+const INLINE_TABLE_MAX_KEYS: usize = 4;
+
+pub(crate) fn serialize(value: Map<String, Value>, inline_tables: bool) -> Result<String, String> {
     let mut table = toml::Table::new();
     for (key, value) in value {
-        table.insert(key, to_toml_value(value));
+        table.insert(key, to_toml_value(value)?);
+    }
+    let out = toml::to_string(&table).map_err(|e| format!("Failed to serialize TOML: {}", e))?;
+    if !inline_tables {
+        return Ok(out);
     }
-    toml::to_string(&table).unwrap()
+
+    let mut document = out
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| format!("Failed to serialize TOML: {}", e))?;
+    inline_small_tables(document.as_table_mut());
+    Ok(document.to_string())
 }
 
-fn to_toml_value(value: Value) -> toml::Value {
-    match value {
+fn inline_small_tables(table: &mut toml_edit::Table) {
+    for (mut key, item) in table.iter_mut() {
+        let inline = match item.as_table_mut() {
+            Some(inner) => {
+                inline_small_tables(inner);
+                inner.len() <= INLINE_TABLE_MAX_KEYS && inner.iter().all(|(_, i)| i.is_value())
+            }
+            None => false,
+        };
+        if !inline {
+            continue;
+        }
+        if let toml_edit::Item::Table(inner) = std::mem::take(item) {
+            let mut inline_table = inner.into_inline_table();
+            inline_table.fmt();
+            key.leaf_decor_mut().clear();
+            *item = toml_edit::Item::Value(toml_edit::Value::InlineTable(inline_table));
+        }
+    }
+}
+
+fn to_toml_value(value: Value) -> Result<toml::Value, String> {
+    Ok(match value {
         Value::String(s) => toml::Value::String(s),
+        #[cfg(feature = "intern")]
+        Value::Shared(s) => toml::Value::String(s.to_string()),
+        Value::Datetime(dt) => toml::Value::Datetime(
+            dt.parse()
+                .map_err(|_| format!("TOML does not support datetime {}", dt))?,
+        ),
         Value::Int(i) => toml::Value::Integer(i),
+        Value::UInt(u) => return Err(format!("TOML does not support integer {}", u)),
+        #[cfg(feature = "i128")]
+        Value::BigInt(i) => return Err(format!("TOML does not support integer {}", i)),
         Value::Float(f) => toml::Value::Float(f),
         Value::Bool(b) => toml::Value::Boolean(b),
         Value::Array(arr) => {
             let mut values = Vec::new();
             for item in arr {
-                values.push(to_toml_value(item));
+                values.push(to_toml_value(item)?);
             }
             toml::Value::Array(values)
         }
         Value::Table(table) => {
             let mut toml_table = toml::Table::new();
             for (key, value) in table {
-                toml_table.insert(key, to_toml_value(value));
+                toml_table.insert(key, to_toml_value(value)?);
             }
             toml::Value::Table(toml_table)
         }
-        _ => panic!("Unsupported value type for TOML serialization"),
-    }
+        Value::None => return Err("TOML does not support null values".to_string()),
+    })
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_descriptions() {
+        let toml_content = r#"# Not attached
+
+# Port to listen on
+# Defaults to 8080
+port = 8080
+name = "app"
+
+# Database settings
+[db]
+  # Connection string
+  url = "postgres://"
+"#;
+        let descriptions = descriptions(toml_content);
+        assert_eq!(
+            descriptions.get("port").map(String::as_str),
+            Some("Port to listen on\nDefaults to 8080")
+        );
+        assert_eq!(descriptions.get("name"), None);
+        assert_eq!(
+            descriptions.get("db").map(String::as_str),
+            Some("Database settings")
+        );
+        assert_eq!(
+            descriptions.get("db.url").map(String::as_str),
+            Some("Connection string")
+        );
+    }
+
     #[test]
     fn test_invalid() {
         let toml_content = r#"[section"#;
@@ -129,7 +265,7 @@ mod test {
         map.insert("int_key".to_string(), Value::Int(42));
         map.insert("float_key".to_string(), Value::Float(3.1));
         map.insert("bool_key".to_string(), Value::Bool(true));
-        let serialized = serialize(map);
+        let serialized = serialize(map, false).unwrap();
         assert!(serialized.contains("key = \"value\""));
         assert!(serialized.contains("int_key = 42"));
         assert!(serialized.contains("float_key = 3.1"));
@@ -143,10 +279,54 @@ mod test {
             "array_key".to_string(),
             Value::Array(vec![Value::Int(1), Value::Int(2), Value::Int(3)]),
         );
-        let serialized = serialize(map);
+        let serialized = serialize(map, false).unwrap();
         assert!(serialized.contains("array_key = [1, 2, 3]"));
     }
 
+    #[test]
+    fn test_serialize_inline_tables() {
+        let small = Map::from_iter(vec![("a".to_string(), Value::Int(1))]);
+        let large = Map::from_iter((0..5).map(|i| (format!("k{}", i), Value::Int(i))));
+        let nested = Map::from_iter(vec![("inner".to_string(), Value::Table(small.clone()))]);
+        let mut map = Map::new();
+        map.insert("small".to_string(), Value::Table(small));
+        map.insert("large".to_string(), Value::Table(large));
+        map.insert("nested".to_string(), Value::Table(nested));
+        let holds_large = Map::from_iter(vec![("big".to_string(), map["large"].clone())]);
+        map.insert("holds_large".to_string(), Value::Table(holds_large));
+        let serialized = serialize(map, true).unwrap();
+        assert!(serialized.contains("small = { a = 1 }"));
+        assert!(serialized.contains("[large]"));
+        assert!(serialized.contains("nested = { inner = { a = 1 } }"));
+        assert!(serialized.contains("[holds_large.big]"));
+    }
+
+    #[test]
+    fn test_datetime_round_trip() {
+        let content = "created = 2023-01-01T00:00:00Z\nday = 1979-05-27\n";
+        let map = deserialize(content.to_string()).unwrap();
+        assert_eq!(
+            map.get("created").unwrap(),
+            &Value::Datetime("2023-01-01T00:00:00Z".to_string())
+        );
+        assert_eq!(
+            map.get("day").unwrap(),
+            &Value::Datetime("1979-05-27".to_string())
+        );
+        let serialized = serialize(map.clone(), false).unwrap();
+        assert_eq!(serialized, content);
+        assert_eq!(deserialize(serialized).unwrap(), map);
+    }
+
+    #[test]
+    fn test_serialize_invalid_datetime() {
+        let map = Map::from_iter([("at".to_string(), Value::Datetime("noon".to_string()))]);
+        assert_eq!(
+            serialize(map, false),
+            Err("TOML does not support datetime noon".to_string())
+        );
+    }
+
     mod from_toml_value {
         use super::*;
 
@@ -212,14 +392,14 @@ mod test {
         #[test]
         fn test_to_toml_value() {
             let value = Value::String("value".to_string());
-            let toml_value = to_toml_value(value);
+            let toml_value = to_toml_value(value).unwrap();
             assert_eq!(toml_value, toml::Value::String("value".to_string()));
         }
 
         #[test]
         fn test_to_toml_array() {
             let value = Value::Array(vec![Value::Int(1), Value::String("two".to_string())]);
-            let toml_value = to_toml_value(value);
+            let toml_value = to_toml_value(value).unwrap();
             assert_eq!(
                 toml_value,
                 toml::Value::Array(vec![
@@ -234,7 +414,7 @@ mod test {
             let mut map = Map::new();
             map.insert("key".to_string(), Value::String("value".to_string()));
             let value = Value::Table(map);
-            let toml_value = to_toml_value(value);
+            let toml_value = to_toml_value(value).unwrap();
             let mut expected_table = toml::Table::new();
             expected_table.insert("key".to_string(), toml::Value::String("value".to_string()));
             assert_eq!(toml_value, toml::Value::Table(expected_table));
@@ -243,35 +423,35 @@ mod test {
         #[test]
         fn test_to_toml_bool() {
             let value = Value::Bool(true);
-            let toml_value = to_toml_value(value);
+            let toml_value = to_toml_value(value).unwrap();
             assert_eq!(toml_value, toml::Value::Boolean(true));
         }
 
         #[test]
         fn test_to_toml_integer() {
             let value = Value::Int(42);
-            let toml_value = to_toml_value(value);
+            let toml_value = to_toml_value(value).unwrap();
             assert_eq!(toml_value, toml::Value::Integer(42));
         }
 
         #[test]
         fn test_to_toml_float() {
             let value = Value::Float(3.1);
-            let toml_value = to_toml_value(value);
+            let toml_value = to_toml_value(value).unwrap();
             assert_eq!(toml_value, toml::Value::Float(3.1));
         }
 
         #[test]
         fn test_to_toml_string() {
             let value = Value::String("Hello".to_string());
-            let toml_value = to_toml_value(value);
+            let toml_value = to_toml_value(value).unwrap();
             assert_eq!(toml_value, toml::Value::String("Hello".to_string()));
         }
 
         #[test]
         fn test_to_toml_unsupported() {
             let value = Value::None;
-            let result = std::panic::catch_unwind(|| to_toml_value(value));
+            let result = to_toml_value(value);
             assert!(result.is_err());
         }
     }
@@ -1,8 +1,10 @@
+use crate::config::YamlStyle;
 use crate::value::{Map, Table, Value};
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
 
 pub(crate) fn deserialize(content: String) -> Result<Map<String, Value>, String> {
-    let mut yaml_content = yaml_rust2::YamlLoader::load_from_str(&content)
-        .map_err(|e| format!("Failed to parse YAML: {}", e))?;
+    let mut yaml_content = load_typed(&content)?;
     let root = match yaml_content.len() {
         0 => yaml_rust2::Yaml::Hash(yaml_rust2::yaml::Hash::new()),
         1 => std::mem::replace(&mut yaml_content[0], yaml_rust2::Yaml::Null),
@@ -16,7 +18,8 @@ pub(crate) fn deserialize(content: String) -> Result<Map<String, Value>, String>
         yaml_rust2::Yaml::Hash(hash) => {
             for (key, value) in hash {
                 if let yaml_rust2::Yaml::String(key_str) = key {
-                    map.insert(key_str, from_yaml_value(&value));
+                    let value = from_yaml_value(&value, &key_str)?;
+                    map.insert(key_str, value);
                 } else {
                     return Err("YAML keys must be strings".to_string());
                 }
@@ -27,46 +30,215 @@ pub(crate) fn deserialize(content: String) -> Result<Map<String, Value>, String>
     Ok(map)
 }
 
-fn from_yaml_value(value: &yaml_rust2::Yaml) -> Value {
-    match value {
+/// The text of the plain scalar under `keys` exactly as written, like `1.10` or `01234`.
+pub(crate) fn raw_scalar(content: &str, keys: &[String]) -> Option<String> {
+    let mut loader = TypedLoader {
+        raw: true,
+        ..TypedLoader::default()
+    };
+    Parser::new_from_str(content)
+        .load(&mut loader, false)
+        .ok()?;
+    let root = loader.loader.documents().first()?;
+    match keys.iter().fold(root, |yaml, key| &yaml[key.as_str()]) {
+        yaml_rust2::Yaml::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Loader that honors core schema tags (`!!int`, `!!float`, `!!bool`, `!!null`) on quoted
+/// scalars, which `YamlLoader` otherwise always keeps as strings. With `raw` set, untagged
+/// plain scalars are kept as strings instead.
+#[derive(Default)]
+struct TypedLoader {
+    loader: yaml_rust2::YamlLoader,
+    raw: bool,
+    /// Documents ended so far, more than the loader holds if it stopped at an error
+    documents: usize,
+}
+
+impl MarkedEventReceiver for TypedLoader {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        if ev == Event::DocumentEnd {
+            self.documents += 1;
+        }
+        let ev = match ev {
+            Event::Scalar(v, TScalarStyle::Plain, aid, None) if self.raw => {
+                Event::Scalar(v, TScalarStyle::SingleQuoted, aid, None)
+            }
+            Event::Scalar(v, style, aid, Some(tag))
+                if style != TScalarStyle::Plain
+                    && tag.handle == "tag:yaml.org,2002:"
+                    && matches!(tag.suffix.as_str(), "int" | "float" | "bool" | "null") =>
+            {
+                Event::Scalar(v, TScalarStyle::Plain, aid, Some(tag))
+            }
+            ev => ev,
+        };
+        self.loader.on_event(ev, mark);
+    }
+}
+
+fn load_typed(content: &str) -> Result<Vec<yaml_rust2::Yaml>, String> {
+    let mut loader = TypedLoader::default();
+    Parser::new_from_str(content)
+        .load(&mut loader, true)
+        .map_err(|e| format!("Failed to parse YAML: {}", e))?;
+    if loader.loader.documents().len() != loader.documents {
+        // A loader driven by hand stops at errors like duplicated keys without exposing
+        // them, so only then the stock loader is run to report the error.
+        return Err(match yaml_rust2::YamlLoader::load_from_str(content) {
+            Err(e) => format!("Failed to parse YAML: {}", e),
+            Ok(_) => "Failed to parse YAML".to_string(),
+        });
+    }
+    Ok(loader.loader.documents().to_vec())
+}
+
+/// Converts a parsed YAML value, failing on scalars that don't match their tag like
+/// `!!int "abc"`. `path` names the value in the error.
+fn from_yaml_value(value: &yaml_rust2::Yaml, path: &str) -> Result<Value, String> {
+    Ok(match value {
         yaml_rust2::Yaml::Null => Value::None,
         yaml_rust2::Yaml::Boolean(b) => Value::Bool(*b),
         yaml_rust2::Yaml::Integer(i) => Value::Int(*i),
         yaml_rust2::Yaml::Real(n) => {
             if let Ok(i) = n.parse::<i64>() {
                 Value::Int(i)
+            } else if let Ok(u) = n.parse::<u64>() {
+                Value::UInt(u)
+            } else if let Some(i) = parse_big_int(n) {
+                i
             } else {
-                Value::Float(n.parse::<f64>().unwrap_or(0.0))
+                let float = parse_float(n)
+                    .ok_or_else(|| format!("Invalid YAML float {} for {}", n, path))?;
+                Value::Float(float)
             }
         }
         yaml_rust2::Yaml::String(s) => Value::String(s.clone()),
         yaml_rust2::Yaml::Array(arr) => {
             let mut values = Vec::new();
-            for item in arr {
-                values.push(from_yaml_value(item));
+            for (i, item) in arr.iter().enumerate() {
+                values.push(from_yaml_value(item, &format!("{}[{}]", path, i))?);
             }
             Value::Array(values)
         }
         yaml_rust2::Yaml::Hash(obj) => {
             let mut table = Table::new();
             for (key, value) in obj {
-                table.insert(
-                    key.clone().as_str().unwrap().to_string(),
-                    from_yaml_value(value),
-                );
+                let key = key_to_string(key, path)?;
+                let value = from_yaml_value(value, &format!("{}.{}", path, key))?;
+                table.insert(key, value);
             }
             Value::Table(table)
         }
-        _ => Value::None,
+        yaml_rust2::Yaml::BadValue => {
+            return Err(format!(
+                "Invalid YAML value for {}, it does not match its tag",
+                path
+            ));
+        }
+        yaml_rust2::Yaml::Alias(_) => Value::None,
+    })
+}
+
+/// Integers outside of the `i64` and `u64` ranges are kept as `BigInt` with the `i128` feature,
+/// and read as floats like JSON does otherwise.
+#[cfg(feature = "i128")]
+fn parse_big_int(n: &str) -> Option<Value> {
+    n.parse::<i128>().ok().map(Value::from)
+}
+
+#[cfg(not(feature = "i128"))]
+fn parse_big_int(_n: &str) -> Option<Value> {
+    None
+}
+
+/// Parses a float, including the YAML spellings `.inf`, `+.inf`, `-.inf` and `.nan` in any case.
+fn parse_float(n: &str) -> Option<f64> {
+    match n.to_lowercase().as_str() {
+        ".inf" | "+.inf" => Some(f64::INFINITY),
+        "-.inf" => Some(f64::NEG_INFINITY),
+        ".nan" => Some(f64::NAN),
+        _ => n.parse().ok(),
     }
 }
 
-pub(crate) fn serialize(value: Map<String, Value>) -> String {
+/// Converts a nested mapping key to a string: `true` becomes `"true"`, `1` becomes `"1"` and
+/// `~` becomes `"null"`. Other keys use the `Value` display, so `[1, 2]` becomes `"[1, 2]"`.
+fn key_to_string(key: &yaml_rust2::Yaml, path: &str) -> Result<String, String> {
+    Ok(match key {
+        yaml_rust2::Yaml::String(s) | yaml_rust2::Yaml::Real(s) => s.clone(),
+        yaml_rust2::Yaml::Integer(i) => i.to_string(),
+        yaml_rust2::Yaml::Boolean(b) => b.to_string(),
+        yaml_rust2::Yaml::Null => "null".to_string(),
+        other => from_yaml_value(other, path)?.to_string(),
+    })
+}
+
+pub(crate) fn serialize(
+    value: Map<String, Value>,
+    style: YamlStyle,
+    document_marker: bool,
+) -> String {
     let yaml_value = to_yaml_value(value);
     let mut out_str = String::new();
-    let mut emitter = yaml_rust2::YamlEmitter::new(&mut out_str);
-    emitter.dump(&yaml_value).unwrap();
-    out_str
+    match style {
+        YamlStyle::Block => {
+            let mut emitter = yaml_rust2::YamlEmitter::new(&mut out_str);
+            emitter.dump(&yaml_value).unwrap();
+        }
+        YamlStyle::Flow => {
+            out_str.push_str("--- ");
+            write_flow(&mut out_str, &yaml_value);
+        }
+    }
+    if document_marker {
+        return out_str;
+    }
+    strip_document_marker(&out_str).to_string()
+}
+
+/// Removes the leading `---` that `YamlEmitter` always writes.
+fn strip_document_marker(yaml: &str) -> &str {
+    yaml.strip_prefix("---\n")
+        .or_else(|| yaml.strip_prefix("--- "))
+        .unwrap_or(yaml)
+}
+
+/// Writes `yaml` on a single line in flow style, `{key: [1, 2]}`. Scalars go through
+/// `YamlEmitter` so they are quoted the same way as in block style.
+fn write_flow(out: &mut String, yaml: &yaml_rust2::Yaml) {
+    match yaml {
+        yaml_rust2::Yaml::Hash(hash) => {
+            out.push('{');
+            for (i, (key, value)) in hash.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_flow(out, key);
+                out.push_str(": ");
+                write_flow(out, value);
+            }
+            out.push('}');
+        }
+        yaml_rust2::Yaml::Array(array) => {
+            out.push('[');
+            for (i, value) in array.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_flow(out, value);
+            }
+            out.push(']');
+        }
+        scalar => {
+            let mut scalar_str = String::new();
+            let mut emitter = yaml_rust2::YamlEmitter::new(&mut scalar_str);
+            emitter.dump(scalar).unwrap();
+            out.push_str(strip_document_marker(&scalar_str));
+        }
+    }
 }
 
 fn to_yaml_value(value: Map<String, Value>) -> yaml_rust2::Yaml {
@@ -78,13 +250,30 @@ fn to_yaml_value(value: Map<String, Value>) -> yaml_rust2::Yaml {
     )
 }
 
+/// Formats a float so it is read back as a float, `1.0` instead of `1`.
+fn float_to_yaml(f: f64) -> String {
+    if f.is_nan() {
+        ".nan".to_string()
+    } else if f.is_infinite() {
+        if f > 0.0 { ".inf" } else { "-.inf" }.to_string()
+    } else {
+        format!("{:?}", f)
+    }
+}
+
 fn to_yaml_value_single(value: Value) -> yaml_rust2::Yaml {
     match value {
         Value::None => yaml_rust2::Yaml::Null,
         Value::Bool(b) => yaml_rust2::Yaml::Boolean(b),
         Value::Int(i) => yaml_rust2::Yaml::Integer(i),
-        Value::Float(f) => yaml_rust2::Yaml::Real(f.to_string()),
+        Value::UInt(u) => yaml_rust2::Yaml::Real(u.to_string()),
+        #[cfg(feature = "i128")]
+        Value::BigInt(i) => yaml_rust2::Yaml::Real(i.to_string()),
+        Value::Float(f) => yaml_rust2::Yaml::Real(float_to_yaml(f)),
         Value::String(s) => yaml_rust2::Yaml::String(s),
+        #[cfg(feature = "intern")]
+        Value::Shared(s) => yaml_rust2::Yaml::String(s.to_string()),
+        Value::Datetime(dt) => yaml_rust2::Yaml::String(dt),
         Value::Array(arr) => {
             yaml_rust2::Yaml::Array(arr.into_iter().map(to_yaml_value_single).collect())
         }
@@ -141,6 +330,22 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_nested_non_string_keys() {
+        let input = "flags:\n  true: on\n  1: one\n  1.5: half\n  ~: nothing\n  2021-01-01: date";
+        let map = deserialize(input.to_string()).unwrap();
+        assert_eq!(
+            map["flags"],
+            Value::Table(Table::from_iter([
+                ("true".to_string(), Value::from("on")),
+                ("1".to_string(), Value::from("one")),
+                ("1.5".to_string(), Value::from("half")),
+                ("null".to_string(), Value::from("nothing")),
+                ("2021-01-01".to_string(), Value::from("date")),
+            ]))
+        );
+    }
+
     #[test]
     fn test_non_string_keys() {
         let input = "123: value";
@@ -162,6 +367,89 @@ key: value"#;
             )])
         );
     }
+
+    #[test]
+    fn test_deserialize_tags() {
+        let yaml_string = r#"
+str_key: !!str 123
+int_key: !!int "10"
+float_key: !!float '2.5'
+bool_key: !!bool "true"
+custom_key: !custom value"#;
+        let parsed_map = deserialize(yaml_string.to_string()).unwrap();
+        assert_eq!(
+            parsed_map.get("str_key").unwrap(),
+            &Value::String("123".to_string())
+        );
+        assert_eq!(parsed_map.get("int_key").unwrap(), &Value::Int(10));
+        assert_eq!(parsed_map.get("float_key").unwrap(), &Value::Float(2.5));
+        assert_eq!(parsed_map.get("bool_key").unwrap(), &Value::Bool(true));
+        assert_eq!(
+            parsed_map.get("custom_key").unwrap(),
+            &Value::String("value".to_string())
+        );
+    }
+
+    #[test]
+    fn test_round_trip_special_floats() {
+        let map = Map::from_iter([
+            ("inf".to_string(), Value::Float(f64::INFINITY)),
+            ("neg_inf".to_string(), Value::Float(f64::NEG_INFINITY)),
+            ("nan".to_string(), Value::Float(f64::NAN)),
+        ]);
+        let parsed = deserialize(serialize(map, YamlStyle::Block, true)).unwrap();
+        assert_eq!(parsed["inf"], Value::Float(f64::INFINITY));
+        assert_eq!(parsed["neg_inf"], Value::Float(f64::NEG_INFINITY));
+        assert!(matches!(parsed["nan"], Value::Float(f) if f.is_nan()));
+
+        let parsed = deserialize("a: .INF\nb: +.Inf\nc: .NaN\nd: inf".to_string()).unwrap();
+        assert_eq!(parsed["a"], Value::Float(f64::INFINITY));
+        assert_eq!(parsed["b"], Value::Float(f64::INFINITY));
+        assert!(matches!(parsed["c"], Value::Float(f) if f.is_nan()));
+        assert_eq!(parsed["d"], Value::Float(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_deserialize_big_ints() {
+        let parsed = deserialize(
+            "u: 18446744073709551615\nbig: 99999999999999999999999\nneg: -99999999999999999999999"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(parsed["u"], Value::UInt(u64::MAX));
+        #[cfg(not(feature = "i128"))]
+        {
+            assert_eq!(parsed["big"], Value::Float(1e23));
+            assert_eq!(parsed["neg"], Value::Float(-1e23));
+        }
+        #[cfg(feature = "i128")]
+        {
+            assert_eq!(parsed["big"], Value::BigInt(99999999999999999999999));
+            assert_eq!(parsed["neg"], Value::BigInt(-99999999999999999999999));
+        }
+    }
+
+    #[test]
+    fn test_deserialize_bad_tag() {
+        let result = deserialize("x: !!int \"abc\"".to_string());
+        assert_eq!(
+            result.unwrap_err(),
+            "Invalid YAML value for x, it does not match its tag"
+        );
+        let result = deserialize("a:\n  b: [1, !!float \"no\"]".to_string());
+        assert_eq!(
+            result.unwrap_err(),
+            "Invalid YAML value for a.b[1], it does not match its tag"
+        );
+    }
+
+    #[test]
+    fn test_deserialize_duplicated_key() {
+        let yaml_string = "key: 1\nkey: 2";
+        let result = deserialize(yaml_string.to_string());
+        assert!(result.unwrap_err().contains("duplicated key in mapping"));
+    }
+
     #[test]
     fn test_deserialize_array() {
         let yaml_string = r#"---
@@ -175,7 +463,7 @@ key: value"#;
     fn test_serialize() {
         let mut map = Map::new();
         map.insert("key".to_string(), Value::String("value".to_string()));
-        let yaml_string = serialize(map);
+        let yaml_string = serialize(map, YamlStyle::Block, true);
         assert_eq!(
             yaml_string,
             r#"---
@@ -190,7 +478,7 @@ key: value"#
             "array".to_string(),
             Value::Array(vec![Value::Int(1), Value::String("two".to_string())]),
         );
-        let yaml_string = serialize(map);
+        let yaml_string = serialize(map, YamlStyle::Block, true);
         assert_eq!(
             yaml_string,
             r#"---
@@ -206,38 +494,44 @@ array:
         #[test]
         fn test_from_null() {
             let yaml_value = yaml_rust2::Yaml::Null;
-            let parsed_value = from_yaml_value(&yaml_value);
+            let parsed_value = from_yaml_value(&yaml_value, "key").unwrap();
             assert_eq!(parsed_value, Value::None);
         }
 
         #[test]
         fn test_from_bool() {
             let yaml_value = yaml_rust2::Yaml::Boolean(true);
-            let parsed_value = from_yaml_value(&yaml_value);
+            let parsed_value = from_yaml_value(&yaml_value, "key").unwrap();
             assert_eq!(parsed_value, Value::Bool(true));
         }
 
         #[test]
         fn test_from_int() {
             let yaml_value = yaml_rust2::Yaml::Integer(42);
-            let parsed_value = from_yaml_value(&yaml_value);
+            let parsed_value = from_yaml_value(&yaml_value, "key").unwrap();
             assert_eq!(parsed_value, Value::Int(42));
         }
 
         #[test]
         fn test_from_float() {
             let yaml_value = yaml_rust2::Yaml::Real("3.1".to_string());
-            let parsed_value = from_yaml_value(&yaml_value);
+            let parsed_value = from_yaml_value(&yaml_value, "key").unwrap();
             assert_eq!(parsed_value, Value::Float(3.1));
             let yaml_value = yaml_rust2::Yaml::Real("42".to_string());
-            let parsed_value = from_yaml_value(&yaml_value);
+            let parsed_value = from_yaml_value(&yaml_value, "key").unwrap();
             assert_eq!(parsed_value, Value::Int(42));
+            let yaml_value = yaml_rust2::Yaml::Real("1.2.3".to_string());
+            let parsed_value = from_yaml_value(&yaml_value, "key");
+            assert_eq!(
+                parsed_value.unwrap_err(),
+                "Invalid YAML float 1.2.3 for key"
+            );
         }
 
         #[test]
         fn test_from_string() {
             let yaml_value = yaml_rust2::Yaml::String("Hello".to_string());
-            let parsed_value = from_yaml_value(&yaml_value);
+            let parsed_value = from_yaml_value(&yaml_value, "key").unwrap();
             assert_eq!(parsed_value, Value::String("Hello".to_string()));
         }
 
@@ -247,7 +541,7 @@ array:
                 yaml_rust2::Yaml::Integer(1),
                 yaml_rust2::Yaml::String("two".to_string()),
             ]);
-            let parsed_value = from_yaml_value(&yaml_value);
+            let parsed_value = from_yaml_value(&yaml_value, "key").unwrap();
             assert_eq!(
                 parsed_value,
                 Value::Array(vec![Value::Int(1), Value::String("two".to_string())])
@@ -265,7 +559,7 @@ array:
                 .cloned()
                 .collect(),
             );
-            let parsed_value = from_yaml_value(&yaml_value);
+            let parsed_value = from_yaml_value(&yaml_value, "key").unwrap();
             assert_eq!(
                 parsed_value,
                 Value::Table(Table::from_iter(vec![(
@@ -278,8 +572,11 @@ array:
         #[test]
         fn test_from_bad_value() {
             let yaml_value = yaml_rust2::Yaml::BadValue;
-            let parsed_value = from_yaml_value(&yaml_value);
-            assert_eq!(parsed_value, Value::None);
+            let parsed_value = from_yaml_value(&yaml_value, "key");
+            assert_eq!(
+                parsed_value.unwrap_err(),
+                "Invalid YAML value for key, it does not match its tag"
+            );
         }
     }
 
@@ -314,6 +611,17 @@ array:
             assert_eq!(yaml_value, yaml_rust2::Yaml::Real("3.1".to_string()));
         }
 
+        #[test]
+        fn test_whole_float_to_yaml_value_single() {
+            let value = Value::Float(1.0);
+            let yaml_value = to_yaml_value_single(value);
+            assert_eq!(yaml_value, yaml_rust2::Yaml::Real("1.0".to_string()));
+            let yaml_value = to_yaml_value_single(Value::Float(f64::NEG_INFINITY));
+            assert_eq!(yaml_value, yaml_rust2::Yaml::Real("-.inf".to_string()));
+            let yaml_value = to_yaml_value_single(Value::Float(f64::NAN));
+            assert_eq!(yaml_value, yaml_rust2::Yaml::Real(".nan".to_string()));
+        }
+
         #[test]
         fn test_string_to_yaml_value_single() {
             let value = Value::String("Hello".to_string());
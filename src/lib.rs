@@ -2,8 +2,11 @@
 //!
 //! Configuration is stored in a `Config` structure. It can be created using a builder.
 //! ```rust
+//! #[cfg(feature = "std")]
+//! {
 //! use ronf::Config;
 //! let config = Config::builder().build().unwrap();
+//! }
 //! ```
 //!
 //! On the builder there is a function `add_file(file: File)` which adds a file to read
@@ -28,13 +31,42 @@
 //! ```
 //!
 //! Check `examples/saves.rs` to see how to save changes to a config.
+//!
+//! Without the default `std` feature the crate is `no_std` + `alloc` and only provides `Value`
+//! and its conversions.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
+mod build_info;
+#[cfg(feature = "std")]
 mod config;
+#[cfg(feature = "std")]
+mod de;
 pub mod error;
+#[cfg(feature = "std")]
 mod file;
+#[cfg(feature = "std")]
 mod format;
+#[cfg(feature = "std")]
+mod path;
+pub mod prelude;
+#[cfg(feature = "std")]
+mod ser;
 mod value;
+#[cfg(feature = "std")]
+mod warning;
 
-pub use crate::config::{Config, ConfigBuilder};
-pub use crate::file::{File, FileFormat};
-pub use crate::value::Value;
+pub use crate::build_info::{BuildInfo, build_info};
+#[cfg(feature = "std")]
+pub use crate::config::{
+    ArrayMergePolicy, Config, ConfigBuilder, ConfigSnapshot, ConflictPolicy, YamlStyle, convert,
+};
+#[cfg(feature = "read_file")]
+pub use crate::file::stream_records;
+#[cfg(feature = "std")]
+pub use crate::file::{File, FileFormat, RepeatedKeyPolicy};
+pub use crate::value::{Array, Map, NumOrStr, Table, Value, ValueKind};
+#[cfg(feature = "std")]
+pub use crate::warning::Warning;
@@ -0,0 +1,58 @@
+//! Tokenizer for dotted paths like `server.tls.cert`
+
+/// Splits a dotted path into its keys. A key wrapped in double quotes may contain dots,
+/// with `\"` and `\\` escaping a quote and a backslash inside of it, so `"my.key".sub`
+/// is `["my.key", "sub"]`. An unterminated quote runs to the end of the path.
+pub(crate) fn split_path(path: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut key = String::new();
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => keys.push(std::mem::take(&mut key)),
+            '"' if key.is_empty() => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => key.extend(chars.next()),
+                        '"' => break,
+                        c => key.push(c),
+                    }
+                }
+            }
+            c => key.push(c),
+        }
+    }
+    keys.push(key);
+    keys
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_path_plain() {
+        assert_eq!(split_path("a"), vec!["a"]);
+        assert_eq!(split_path("a.b.c"), vec!["a", "b", "c"]);
+        assert_eq!(split_path("a..b"), vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn test_split_path_quoted() {
+        assert_eq!(split_path(r#""my.key""#), vec!["my.key"]);
+        assert_eq!(split_path(r#""my.key".sub"#), vec!["my.key", "sub"]);
+        assert_eq!(
+            split_path(r#"server."tls.v1".cert"#),
+            vec!["server", "tls.v1", "cert"]
+        );
+        assert_eq!(split_path(r#"a."".b"#), vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn test_split_path_escapes() {
+        assert_eq!(split_path(r#""say \"hi\"".x"#), vec![r#"say "hi""#, "x"]);
+        assert_eq!(split_path(r#""back\\slash""#), vec![r"back\slash"]);
+        assert_eq!(split_path(r#""open.ended"#), vec!["open.ended"]);
+        assert_eq!(split_path(r#"mid"quote"#), vec![r#"mid"quote"#]);
+    }
+}
@@ -0,0 +1,17 @@
+//! Commonly used types, to be glob imported with `use ronf::prelude::*;`
+//! ```rust
+//! #[cfg(feature = "json")]
+//! {
+//! use ronf::prelude::*;
+//! let file = File::new_str("config.json", FileFormat::Json, "{\"key\": 1}");
+//! let config = Config::builder().add_file(file).build().unwrap();
+//! assert_eq!(config.get("key"), Some(&Value::Int(1)));
+//! }
+//! ```
+
+#[cfg(feature = "std")]
+pub use crate::config::{Config, ConfigBuilder};
+pub use crate::error::CannotConvert;
+#[cfg(feature = "std")]
+pub use crate::file::{File, FileFormat};
+pub use crate::value::Value;
@@ -0,0 +1,466 @@
+//! Serde bridge for turning typed structs into values
+
+use serde::ser::{self, Serialize};
+
+use crate::error::ConfigError;
+use crate::value::{Array, Table, Value};
+
+impl ser::Error for ConfigError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        ConfigError::Serialize(msg.to_string())
+    }
+}
+
+/// Serializes any `T: Serialize` into a `Value`.
+pub(crate) fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, ConfigError> {
+    value.serialize(ValueSerializer)
+}
+
+/// Writes a value with its natural serde shape: tables as maps, arrays as sequences and
+/// `None` as a unit, so `serde_json` writes it as `null`.
+impl Serialize for Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::None => serializer.serialize_none(),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Int(i) => serializer.serialize_i64(*i),
+            Value::UInt(u) => serializer.serialize_u64(*u),
+            #[cfg(feature = "i128")]
+            Value::BigInt(i) => serializer.serialize_i128(*i),
+            Value::Float(f) => serializer.serialize_f64(*f),
+            Value::String(s) => serializer.serialize_str(s),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => serializer.serialize_str(s),
+            Value::Datetime(dt) => serializer.serialize_str(dt),
+            Value::Array(array) => serializer.collect_seq(array),
+            Value::Table(table) => serializer.collect_map(table),
+        }
+    }
+}
+
+struct ValueSerializer;
+
+/// Collects a sequence, optionally wrapped into `{ variant: [...] }`.
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    items: Array,
+}
+
+/// Collects a map or struct, optionally wrapped into `{ variant: {...} }`.
+struct TableSerializer {
+    variant: Option<&'static str>,
+    table: Table,
+    next_key: Option<String>,
+}
+
+fn wrap_variant(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        Some(variant) => Value::Table(Table::from_iter([(variant.to_string(), value)])),
+        None => value,
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = ConfigError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = TableSerializer;
+    type SerializeStruct = TableSerializer;
+    type SerializeStructVariant = TableSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ConfigError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, ConfigError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, ConfigError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ConfigError> {
+        Ok(Value::Array(v.iter().map(|b| Value::from(*b)).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Value, ConfigError> {
+        Ok(Value::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, ConfigError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, ConfigError> {
+        Ok(Value::None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ConfigError> {
+        Ok(Value::None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, ConfigError> {
+        Ok(Value::from(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, ConfigError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, ConfigError> {
+        Ok(wrap_variant(Some(variant), value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, ConfigError> {
+        Ok(SeqSerializer {
+            variant: None,
+            items: Array::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, ConfigError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, ConfigError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, ConfigError> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            items: Array::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<TableSerializer, ConfigError> {
+        Ok(TableSerializer {
+            variant: None,
+            table: Table::with_capacity(len.unwrap_or(0)),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<TableSerializer, ConfigError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<TableSerializer, ConfigError> {
+        Ok(TableSerializer {
+            variant: Some(variant),
+            table: Table::with_capacity(len),
+            next_key: None,
+        })
+    }
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConfigError> {
+        self.items.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ConfigError> {
+        Ok(wrap_variant(self.variant, Value::Array(self.items)))
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = ConfigError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConfigError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConfigError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = ConfigError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConfigError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConfigError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = ConfigError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConfigError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConfigError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Value;
+    type Error = ConfigError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConfigError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConfigError> {
+        self.finish()
+    }
+}
+
+impl TableSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), ConfigError> {
+        self.table.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ConfigError> {
+        Ok(wrap_variant(self.variant, Value::Table(self.table)))
+    }
+}
+
+impl ser::SerializeMap for TableSerializer {
+    type Ok = Value;
+    type Error = ConfigError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ConfigError> {
+        let key = match to_value(key)? {
+            Value::String(s) => s,
+            Value::Int(i) => i.to_string(),
+            Value::UInt(u) => u.to_string(),
+            Value::Float(f) => f.to_string(),
+            Value::Bool(b) => b.to_string(),
+            other => {
+                return Err(ConfigError::Serialize(format!(
+                    "map key must be a string, found {}",
+                    other.kind()
+                )));
+            }
+        };
+        self.next_key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConfigError> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| ConfigError::Serialize("map value without a key".to_string()))?;
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<Value, ConfigError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for TableSerializer {
+    type Ok = Value;
+    type Error = ConfigError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ConfigError> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, ConfigError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for TableSerializer {
+    type Ok = Value;
+    type Error = ConfigError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ConfigError> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, ConfigError> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    enum Mode {
+        Fast,
+        Slow { delay: u32 },
+    }
+
+    #[derive(Serialize)]
+    struct Settings {
+        name: String,
+        port: u16,
+        tags: Vec<&'static str>,
+        mode: Mode,
+        fallback: Option<Mode>,
+        limits: BTreeMap<u8, bool>,
+    }
+
+    #[test]
+    fn test_to_value() {
+        let settings = Settings {
+            name: "app".to_string(),
+            port: 8080,
+            tags: vec!["a"],
+            mode: Mode::Fast,
+            fallback: Some(Mode::Slow { delay: 5 }),
+            limits: BTreeMap::from([(1, true)]),
+        };
+        let value = to_value(&settings).unwrap();
+        assert_eq!(
+            value,
+            Value::Table(Table::from_iter([
+                ("name".to_string(), Value::from("app")),
+                ("port".to_string(), Value::Int(8080)),
+                ("tags".to_string(), Value::Array(vec![Value::from("a")])),
+                ("mode".to_string(), Value::from("Fast")),
+                (
+                    "fallback".to_string(),
+                    Value::Table(Table::from_iter([(
+                        "Slow".to_string(),
+                        Value::Table(Table::from_iter([("delay".to_string(), Value::Int(5))])),
+                    )])),
+                ),
+                (
+                    "limits".to_string(),
+                    Value::Table(Table::from_iter([("1".to_string(), Value::Bool(true))])),
+                ),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_serialize_value() {
+        let value = Value::Table(Table::from_iter([
+            ("name".to_string(), Value::from("app")),
+            ("ratio".to_string(), Value::Float(0.5)),
+            ("none".to_string(), Value::None),
+            (
+                "list".to_string(),
+                Value::Array(vec![Value::Int(1), Value::Bool(true)]),
+            ),
+        ]));
+        assert_eq!(to_value(&value).unwrap(), value);
+    }
+
+    #[test]
+    fn test_to_value_invalid_key() {
+        let map = BTreeMap::from([(vec![1], 1)]);
+        assert_eq!(
+            to_value(&map),
+            Err(ConfigError::Serialize(
+                "map key must be a string, found Array".to_string()
+            ))
+        );
+    }
+}
@@ -1,19 +1,59 @@
 //! Definition for `Value`
 
 use crate::error::CannotConvert;
-use std::convert::{From, TryInto};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::{From, TryInto};
 
 /// A type alias for a map that can be either ordered or unordered.
-pub(crate) type Map<K, V> = indexmap::IndexMap<K, V>;
+#[cfg(feature = "std")]
+pub type Map<K, V> = indexmap::IndexMap<K, V>;
+
+/// A type alias for a map that can be either ordered or unordered.
+///
+/// Without `std` there is no `RandomState`, so keys are hashed with an unkeyed FNV-1a hasher.
+#[cfg(not(feature = "std"))]
+pub type Map<K, V> = indexmap::IndexMap<K, V, core::hash::BuildHasherDefault<FnvHasher>>;
+
+/// FNV-1a hasher used by `Map` without `std`. It is unkeyed, so it is only used where
+/// `RandomState` is not available, and is not exported.
+#[cfg(not(feature = "std"))]
+pub struct FnvHasher(u64);
+
+#[cfg(not(feature = "std"))]
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl core::hash::Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
 
 /// A type alias for an Array in a config
-pub(crate) type Array = Vec<Value>;
+pub type Array = Vec<Value>;
 
 /// A type alias for a Table in a config
-pub(crate) type Table = Map<String, Value>;
+pub type Table = Map<String, Value>;
 
 /// A type that represents a value in a configuration file.
-#[derive(Debug, Clone, PartialEq, Default)]
+///
+/// Some variants only exist with a Cargo feature, so the enum is non-exhaustive to keep
+/// enabling features additive: matches outside of this crate need a wildcard arm.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+#[cfg_attr(not(feature = "intern"), derive(PartialEq))]
 pub enum Value {
     #[default]
     None,
@@ -21,8 +61,104 @@ pub enum Value {
     Table(Table),
     String(String),
     Float(f64),
+    /// Integers are stored as `i64`, unsigned ones above `i64::MAX` as `UInt`. Values outside
+    /// of both ranges are stored as `BigInt` with the `i128` feature, and otherwise saturate at
+    /// `i64::MIN` or `u64::MAX` rather than wrapping.
     Int(i64),
+    /// Unsigned integers above `i64::MAX`, read back with `TryInto<u64>`. Values that fit in
+    /// an `i64` are always stored as `Int`.
+    UInt(u64),
     Bool(bool),
+    /// Integers outside of the `i64` and `u64` ranges, only created with the `i128` feature.
+    /// Values that fit in an `i64` or `u64` are always stored as `Int` or `UInt`.
+    #[cfg(feature = "i128")]
+    BigInt(i128),
+    /// A string sharing its storage with equal strings, only created by
+    /// `Config::intern_strings` with the `intern` feature. It behaves like `String` everywhere,
+    /// including comparisons, and is saved as a plain string.
+    #[cfg(feature = "intern")]
+    Shared(alloc::sync::Arc<str>),
+    /// A date and time as written in the file, like `2023-01-01T00:00:00Z`. Only TOML has
+    /// datetimes, other formats save it as a plain string.
+    Datetime(String),
+}
+
+/// Compares like the derived `PartialEq`, except that a `Shared` string equals a `String` with
+/// the same content.
+#[cfg(feature = "intern")]
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::None, Value::None) => true,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Table(a), Value::Table(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Shared(a), Value::Shared(b)) => a == b,
+            (Value::String(a), Value::Shared(b)) | (Value::Shared(b), Value::String(a)) => {
+                a.as_str() == &**b
+            }
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::UInt(a), Value::UInt(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Datetime(a), Value::Datetime(b)) => a == b,
+            #[cfg(feature = "i128")]
+            (Value::BigInt(a), Value::BigInt(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// A value that is either a number or a string, returned by `Value::as_num_or_str`.
+///
+/// Integers keep the variant they have in `Value`, and like `Value` the enum is non-exhaustive
+/// because `BigInt` only exists with the `i128` feature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum NumOrStr<'a> {
+    Int(i64),
+    /// Unsigned integers above `i64::MAX`, see `Value::UInt`.
+    UInt(u64),
+    /// Integers outside of the `i64` and `u64` ranges, see `Value::BigInt`.
+    #[cfg(feature = "i128")]
+    BigInt(i128),
+    Float(f64),
+    Str(&'a str),
+}
+
+/// Kind of a `Value`, used to describe expected types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    None,
+    Array,
+    Table,
+    String,
+    Float,
+    Int,
+    Bool,
+    Datetime,
+}
+
+impl ValueKind {
+    /// Name of the kind, matching the `Value` variant name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueKind::None => "None",
+            ValueKind::Array => "Array",
+            ValueKind::Table => "Table",
+            ValueKind::String => "String",
+            ValueKind::Float => "Float",
+            ValueKind::Int => "Int",
+            ValueKind::Bool => "Bool",
+            ValueKind::Datetime => "Datetime",
+        }
+    }
+}
+
+impl core::fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 impl Value {
@@ -66,6 +202,32 @@ impl Value {
         }
     }
 
+    /// Takes the table out of the value, `None` if it is not a table.
+    pub fn into_table(self) -> Option<Table> {
+        match self {
+            Value::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+
+    /// Takes the array out of the value, `None` if it is not an array.
+    pub fn into_array(self) -> Option<Array> {
+        match self {
+            Value::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// Gets the content of a string value, without converting other kinds.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => Some(s),
+            _ => None,
+        }
+    }
+
     /// Gets a reference to the value associated with the given key in a table.
     pub fn get(&self, key: &str) -> Option<&Value> {
         match self {
@@ -86,10 +248,273 @@ impl Value {
     pub fn is_table(&self) -> bool {
         matches!(self, Value::Table(_))
     }
+
+    /// Checks if the value is none.
+    pub fn is_none(&self) -> bool {
+        matches!(self, Value::None)
+    }
+
+    /// Checks if the value is an array.
+    pub fn is_array(&self) -> bool {
+        matches!(self, Value::Array(_))
+    }
+
+    /// Checks if the value is a string.
+    pub fn is_string(&self) -> bool {
+        self.kind() == ValueKind::String
+    }
+
+    /// Checks if the value is an integer.
+    pub fn is_int(&self) -> bool {
+        self.kind() == ValueKind::Int
+    }
+
+    /// Checks if the value is a float.
+    pub fn is_float(&self) -> bool {
+        matches!(self, Value::Float(_))
+    }
+
+    /// Checks if the value is a bool.
+    pub fn is_bool(&self) -> bool {
+        matches!(self, Value::Bool(_))
+    }
+
+    /// Checks if the value is an integer or a float.
+    pub fn is_number(&self) -> bool {
+        matches!(self.kind(), ValueKind::Int | ValueKind::Float)
+    }
+
+    /// Gets the kind of the value, without its content.
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Value::None => ValueKind::None,
+            Value::Array(_) => ValueKind::Array,
+            Value::Table(_) => ValueKind::Table,
+            Value::String(_) => ValueKind::String,
+            #[cfg(feature = "intern")]
+            Value::Shared(_) => ValueKind::String,
+            Value::Datetime(_) => ValueKind::Datetime,
+            Value::Float(_) => ValueKind::Float,
+            Value::Int(_) | Value::UInt(_) => ValueKind::Int,
+            #[cfg(feature = "i128")]
+            Value::BigInt(_) => ValueKind::Int,
+            Value::Bool(_) => ValueKind::Bool,
+        }
+    }
+
+    /// Converts the value to an integer, only accepting integers and whole-number floats.
+    pub fn as_integer_strict(&self) -> Result<i64, CannotConvert> {
+        match self {
+            Value::Int(n) => Ok(*n),
+            Value::Float(n) if *n % 1.0 == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 => {
+                Ok(*n as i64)
+            }
+            Value::UInt(_) => Err(CannotConvert::new("UInt", "Int")),
+            #[cfg(feature = "i128")]
+            Value::BigInt(_) => Err(CannotConvert::new("BigInt", "Int")),
+            _ => Err(CannotConvert::new(self.kind().as_str(), "Int")),
+        }
+    }
+
+    /// Converts the value to a bool. Numbers are true when non-zero, strings are true when
+    /// one of `t`, `true`, `1`, `y`, `yes` or `on` (ignoring case), and `None` is false.
+    pub fn coerce_bool(&self) -> Result<bool, CannotConvert> {
+        match self {
+            Value::None => Ok(false),
+            Value::String(s) => Ok(is_truthy(s)),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => Ok(is_truthy(s)),
+            Value::Float(n) => Ok(*n != 0.0),
+            Value::Int(n) => Ok(*n != 0),
+            Value::UInt(n) => Ok(*n != 0),
+            #[cfg(feature = "i128")]
+            Value::BigInt(n) => Ok(*n != 0),
+            Value::Array(_) => Err(CannotConvert::new("Array", "Bool")),
+            Value::Table(_) => Err(CannotConvert::new("Table", "Bool")),
+            Value::Datetime(_) => Err(CannotConvert::new("Datetime", "Bool")),
+            Value::Bool(b) => Ok(*b),
+        }
+    }
+
+    /// Recursively upgrades strings that unambiguously hold an integer, a finite float or
+    /// `true`/`false` into typed values, ignoring surrounding whitespace. Strings such as
+    /// `"42abc"`, `"007"` or `"nan"` are left alone.
+    pub fn infer_types(&mut self) {
+        match self {
+            Value::String(s) => {
+                if let Some(value) = infer_string(s.trim()) {
+                    *self = value;
+                }
+            }
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => {
+                if let Some(value) = infer_string(s.trim()) {
+                    *self = value;
+                }
+            }
+            Value::Array(array) => array.iter_mut().for_each(Value::infer_types),
+            Value::Table(table) => table.values_mut().for_each(Value::infer_types),
+            _ => {}
+        }
+    }
+
+    /// Reads a field that may be written as a number or a string, like `30` or `"30s"`.
+    pub fn as_num_or_str(&self) -> Option<NumOrStr<'_>> {
+        match self {
+            Value::Int(n) => Some(NumOrStr::Int(*n)),
+            Value::UInt(n) => Some(NumOrStr::UInt(*n)),
+            #[cfg(feature = "i128")]
+            Value::BigInt(n) => Some(NumOrStr::BigInt(*n)),
+            Value::Float(n) => Some(NumOrStr::Float(*n)),
+            Value::String(s) => Some(NumOrStr::Str(s)),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => Some(NumOrStr::Str(s)),
+            _ => None,
+        }
+    }
+
+    /// Checks that the value can be saved as `format`, reporting the first offending path.
+    /// TOML has no null and INI only holds scalars and arrays of scalars one section deep.
+    /// JSON can't hold `NaN` or infinite floats.
+    #[cfg(feature = "std")]
+    pub fn check_representable(
+        &self,
+        format: crate::file::FileFormat,
+    ) -> Result<(), crate::error::ConfigError> {
+        self.check_representable_at(&format, "", 0)
+            .map_err(|message| crate::error::ConfigError::Format { format, message })
+    }
+
+    #[cfg(feature = "std")]
+    fn check_representable_at(
+        &self,
+        format: &crate::file::FileFormat,
+        path: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        use crate::file::FileFormat;
+
+        let fail = |reason: &str| {
+            Err(match path {
+                "" => reason.to_string(),
+                path => format!("{}: {}", path, reason),
+            })
+        };
+        match (format, self) {
+            (FileFormat::Toml, Value::None) => return fail("TOML does not support null values"),
+            (FileFormat::Toml, Value::UInt(_)) => {
+                return fail("TOML does not support integers outside of the i64 range");
+            }
+            #[cfg(feature = "i128")]
+            (FileFormat::Toml, Value::BigInt(_)) => {
+                return fail("TOML does not support integers outside of the i64 range");
+            }
+            #[cfg(feature = "i128")]
+            (FileFormat::Json, Value::BigInt(_)) => {
+                return fail("JSON does not support integers outside of the i64 and u64 range");
+            }
+            (FileFormat::Json, Value::Float(f)) if !f.is_finite() => {
+                return fail("JSON does not support NaN or infinite floats");
+            }
+            (FileFormat::Toml, value) | (FileFormat::Ini, value)
+                if depth == 0 && !value.is_table() =>
+            {
+                return fail("the root must be a table");
+            }
+            (FileFormat::Ini, Value::Table(_)) if depth > 1 => {
+                return fail("INI does not support nesting beyond one level of sections");
+            }
+            (FileFormat::Ini, Value::Array(_)) if depth > 1 && path.ends_with(']') => {
+                return fail("INI does not support nested arrays");
+            }
+            _ => {}
+        }
+        match self {
+            Value::Array(array) => array.iter().enumerate().try_for_each(|(i, item)| {
+                item.check_representable_at(format, &format!("{}[{}]", path, i), depth + 1)
+            }),
+            Value::Table(table) => table.iter().try_for_each(|(key, value)| {
+                let path = match path {
+                    "" => key.clone(),
+                    path => format!("{}.{}", path, key),
+                };
+                value.check_representable_at(format, &path, depth + 1)
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether a string reads as true for `Value::coerce_bool`.
+fn is_truthy(s: &str) -> bool {
+    parse_bool(s) == Some(true)
+}
+
+/// Reads `t`, `true`, `1`, `y`, `yes` or `on` as true and `f`, `false`, `0`, `n`, `no` or `off`
+/// as false, ignoring case and surrounding whitespace, `None` for anything else.
+pub(crate) fn parse_bool(s: &str) -> Option<bool> {
+    match s.trim().to_lowercase().as_str() {
+        "t" | "true" | "1" | "y" | "yes" | "on" => Some(true),
+        "f" | "false" | "0" | "n" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a string into a typed value for `Value::infer_types`, `None` if it is ambiguous.
+fn infer_string(s: &str) -> Option<Value> {
+    match s.to_lowercase().as_str() {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+        return None;
+    }
+    if let Ok(i) = s.parse::<i64>() {
+        return Some(Value::Int(i));
+    }
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    s.parse::<f64>()
+        .ok()
+        .filter(|f| f.is_finite())
+        .map(Value::Float)
 }
 
-impl std::fmt::Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Value {
+    /// Writes the value over multiple indented lines, used by the `{:#}` format.
+    fn fmt_pretty(&self, f: &mut core::fmt::Formatter<'_>, indent: usize) -> core::fmt::Result {
+        match self {
+            Value::Array(arr) if !arr.is_empty() => {
+                writeln!(f, "[")?;
+                for (i, v) in arr.iter().enumerate() {
+                    write!(f, "{:width$}", "", width = indent + 2)?;
+                    v.fmt_pretty(f, indent + 2)?;
+                    writeln!(f, "{}", if i + 1 < arr.len() { "," } else { "" })?;
+                }
+                write!(f, "{:width$}]", "", width = indent)
+            }
+            Value::Table(table) if !table.is_empty() => {
+                writeln!(f, "{{")?;
+                for (i, (k, v)) in table.iter().enumerate() {
+                    write!(f, "{:width$}{}: ", "", k, width = indent + 2)?;
+                    v.fmt_pretty(f, indent + 2)?;
+                    writeln!(f, "{}", if i + 1 < table.len() { "," } else { "" })?;
+                }
+                write!(f, "{:width$}}}", "", width = indent)
+            }
+            _ => write!(f, "{}", self),
+        }
+    }
+}
+
+impl core::fmt::Display for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if f.alternate() {
+            return self.fmt_pretty(f, 0);
+        }
         match self {
             Value::None => write!(f, "null"),
             Value::Array(arr) => {
@@ -109,8 +534,14 @@ impl std::fmt::Display for Value {
                 write!(f, "{{{}}}", table_str)
             }
             Value::String(s) => write!(f, "\"{}\"", s),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => write!(f, "\"{}\"", s),
+            Value::Datetime(dt) => write!(f, "{}", dt),
             Value::Float(n) => write!(f, "{}", n),
             Value::Int(n) => write!(f, "{}", n),
+            Value::UInt(n) => write!(f, "{}", n),
+            #[cfg(feature = "i128")]
+            Value::BigInt(n) => write!(f, "{}", n),
             Value::Bool(b) => write!(f, "{}", b),
         }
     }
@@ -141,8 +572,14 @@ impl TryInto<String> for Value {
         match self {
             Value::None => Ok("null".to_string()),
             Value::String(s) => Ok(s),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => Ok(s.to_string()),
+            Value::Datetime(dt) => Ok(dt),
             Value::Float(n) => Ok(n.to_string()),
             Value::Int(n) => Ok(n.to_string()),
+            Value::UInt(n) => Ok(n.to_string()),
+            #[cfg(feature = "i128")]
+            Value::BigInt(n) => Ok(n.to_string()),
             Value::Array(_) => Err(CannotConvert::new("Array", "String")),
             Value::Table(_) => Err(CannotConvert::new("Table", "String")),
             Value::Bool(b) => Ok(b.to_string()),
@@ -159,10 +596,18 @@ impl TryInto<f64> for Value {
             Value::String(s) => s
                 .parse::<f64>()
                 .map_err(|_| CannotConvert::new("String", "Float")),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => s
+                .parse::<f64>()
+                .map_err(|_| CannotConvert::new("String", "Float")),
             Value::Float(n) => Ok(n),
             Value::Int(n) => Ok(n as f64),
+            Value::UInt(n) => Ok(n as f64),
+            #[cfg(feature = "i128")]
+            Value::BigInt(n) => Ok(n as f64),
             Value::Array(_) => Err(CannotConvert::new("Array", "Float")),
             Value::Table(_) => Err(CannotConvert::new("Table", "Float")),
+            Value::Datetime(_) => Err(CannotConvert::new("Datetime", "Float")),
             Value::Bool(b) => Ok(if b { 1.0 } else { 0.0 }),
         }
     }
@@ -177,24 +622,84 @@ impl TryInto<i64> for Value {
             Value::String(s) => s
                 .parse::<i64>()
                 .map_err(|_| CannotConvert::new("String", "Int")),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => s
+                .parse::<i64>()
+                .map_err(|_| CannotConvert::new("String", "Int")),
             Value::Float(n) => Ok(n as i64),
             Value::Int(n) => Ok(n),
+            Value::UInt(_) => Err(CannotConvert::new("UInt", "Int")),
+            #[cfg(feature = "i128")]
+            Value::BigInt(_) => Err(CannotConvert::new("BigInt", "Int")),
             Value::Array(_) => Err(CannotConvert::new("Array", "Int")),
             Value::Table(_) => Err(CannotConvert::new("Table", "Int")),
+            Value::Datetime(_) => Err(CannotConvert::new("Datetime", "Int")),
             Value::Bool(b) => Ok(if b { 1 } else { 0 }),
         }
     }
 }
 
+#[cfg(feature = "i128")]
+impl TryInto<i128> for Value {
+    type Error = CannotConvert;
+
+    fn try_into(self) -> Result<i128, Self::Error> {
+        match self {
+            Value::BigInt(n) => Ok(n),
+            Value::UInt(n) => Ok(i128::from(n)),
+            Value::String(s) => s
+                .parse::<i128>()
+                .map_err(|_| CannotConvert::new("String", "BigInt")),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => s
+                .parse::<i128>()
+                .map_err(|_| CannotConvert::new("String", "BigInt")),
+            value => TryInto::<i64>::try_into(value).map(i128::from),
+        }
+    }
+}
+
+/// Negative numbers and values above `u64::MAX` fail instead of wrapping.
+impl TryInto<u64> for Value {
+    type Error = CannotConvert;
+
+    fn try_into(self) -> Result<u64, Self::Error> {
+        let kind = self.kind().as_str();
+        match self {
+            Value::Int(n) => u64::try_from(n).map_err(|_| CannotConvert::new("Int", "UInt")),
+            Value::UInt(n) => Ok(n),
+            #[cfg(feature = "i128")]
+            Value::BigInt(n) => u64::try_from(n).map_err(|_| CannotConvert::new("BigInt", "UInt")),
+            Value::String(s) => s
+                .parse::<u64>()
+                .map_err(|_| CannotConvert::new("String", "UInt")),
+            #[cfg(feature = "intern")]
+            Value::Shared(s) => s
+                .parse::<u64>()
+                .map_err(|_| CannotConvert::new("String", "UInt")),
+            value => TryInto::<i64>::try_into(value)
+                .ok()
+                .and_then(|n| u64::try_from(n).ok())
+                .ok_or_else(|| CannotConvert::new(kind, "UInt")),
+        }
+    }
+}
+
 impl TryInto<Vec<Value>> for Value {
     type Error = CannotConvert;
 
     fn try_into(self) -> Result<Vec<Value>, Self::Error> {
         match self {
-            Value::None => Ok(vec![]),
+            Value::None => Ok(Vec::new()),
             Value::String(_) => Err(CannotConvert::new("String", "Array")),
+            #[cfg(feature = "intern")]
+            Value::Shared(_) => Err(CannotConvert::new("String", "Array")),
+            Value::Datetime(_) => Err(CannotConvert::new("Datetime", "Array")),
             Value::Float(_) => Err(CannotConvert::new("Float", "Array")),
             Value::Int(_) => Err(CannotConvert::new("Int", "Array")),
+            Value::UInt(_) => Err(CannotConvert::new("UInt", "Array")),
+            #[cfg(feature = "i128")]
+            Value::BigInt(_) => Err(CannotConvert::new("BigInt", "Array")),
             Value::Array(arr) => Ok(arr),
             Value::Table(_) => Err(CannotConvert::new("Table", "Array")),
             Value::Bool(_) => Err(CannotConvert::new("Bool", "Array")),
@@ -207,10 +712,16 @@ impl TryInto<Map<String, Value>> for Value {
 
     fn try_into(self) -> Result<Map<String, Value>, Self::Error> {
         match self {
-            Value::None => Ok(Map::new()),
+            Value::None => Ok(Map::default()),
             Value::String(_) => Err(CannotConvert::new("String", "Table")),
+            #[cfg(feature = "intern")]
+            Value::Shared(_) => Err(CannotConvert::new("String", "Table")),
+            Value::Datetime(_) => Err(CannotConvert::new("Datetime", "Table")),
             Value::Float(_) => Err(CannotConvert::new("Float", "Table")),
             Value::Int(_) => Err(CannotConvert::new("Int", "Table")),
+            Value::UInt(_) => Err(CannotConvert::new("UInt", "Table")),
+            #[cfg(feature = "i128")]
+            Value::BigInt(_) => Err(CannotConvert::new("BigInt", "Table")),
             Value::Array(_) => Err(CannotConvert::new("Array", "Table")),
             Value::Table(table) => Ok(table),
             Value::Bool(_) => Err(CannotConvert::new("Bool", "Table")),
@@ -222,21 +733,33 @@ impl TryInto<bool> for Value {
     type Error = CannotConvert;
 
     fn try_into(self) -> Result<bool, Self::Error> {
-        match self {
-            Value::None => Ok(false),
-            Value::String(s) => match s.to_lowercase().as_str() {
-                "t" | "true" | "True" | "1" => Ok(true),
-                _ => Ok(false),
-            },
-            Value::Float(n) => Ok(n != 0.0),
-            Value::Int(n) => Ok(n != 0),
-            Value::Array(_) => Err(CannotConvert::new("Array", "Bool")),
-            Value::Table(_) => Err(CannotConvert::new("Table", "Bool")),
-            Value::Bool(b) => Ok(b),
-        }
+        self.coerce_bool()
     }
 }
 
+/// Implements `TryInto<Option<T>>`, mapping `Value::None` to `None` and converting anything
+/// else with `TryInto<T>`.
+macro_rules! impl_try_into_option {
+    ($($t:ty),*) => {
+        $(
+            impl TryInto<Option<$t>> for Value {
+                type Error = CannotConvert;
+
+                fn try_into(self) -> Result<Option<$t>, Self::Error> {
+                    match self {
+                        Value::None => Ok(None),
+                        value => TryInto::<$t>::try_into(value).map(Some),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_try_into_option!(String, f64, i64, u64, bool);
+#[cfg(feature = "i128")]
+impl_try_into_option!(i128);
+
 impl From<Map<String, Value>> for Value {
     fn from(value: Map<String, Value>) -> Self {
         Value::Table(value)
@@ -261,9 +784,26 @@ impl From<f32> for Value {
     }
 }
 
+/// Stores the value as `Int` or `UInt` when it fits, and as `BigInt` with the `i128` feature.
+/// Without it, values outside of both ranges saturate to `i64::MIN` or `u64::MAX`. Check with
+/// `i64::try_from`/`u64::try_from` first to catch that, and use `Value::check_representable`
+/// before saving, as JSON and TOML can't hold a `BigInt` either.
 impl From<i128> for Value {
     fn from(value: i128) -> Self {
-        Value::Int(value as i64)
+        if let Ok(value) = i64::try_from(value) {
+            return Value::Int(value);
+        }
+        if let Ok(value) = u64::try_from(value) {
+            return Value::UInt(value);
+        }
+        #[cfg(feature = "i128")]
+        return Value::BigInt(value);
+        #[cfg(not(feature = "i128"))]
+        return if value < 0 {
+            Value::Int(i64::MIN)
+        } else {
+            Value::UInt(u64::MAX)
+        };
     }
 }
 
@@ -291,15 +831,21 @@ impl From<i8> for Value {
     }
 }
 
+/// Converts through `From<i128>`, so values above `i128::MAX` saturate to `i128::MAX` with the
+/// `i128` feature and to `u64::MAX` without it. Check with `u64::try_from`/`i128::try_from`
+/// first to catch that, and use `Value::check_representable` before saving.
 impl From<u128> for Value {
     fn from(value: u128) -> Self {
-        Value::Int(value as i64)
+        Value::from(i128::try_from(value).unwrap_or(i128::MAX))
     }
 }
 
 impl From<u64> for Value {
     fn from(value: u64) -> Self {
-        Value::Int(value as i64)
+        match i64::try_from(value) {
+            Ok(value) => Value::Int(value),
+            Err(_) => Value::UInt(value),
+        }
     }
 }
 
@@ -342,6 +888,20 @@ impl From<&[Value]> for Value {
 #[cfg(test)]
 mod test {
     use super::*;
+    #[cfg(feature = "std")]
+    use crate::error::ConfigError;
+
+    #[test]
+    fn test_map_lookup() {
+        let mut map: Map<String, Value> = Map::default();
+        for i in 0..64 {
+            map.insert(format!("key{}", i), Value::Int(i));
+        }
+        assert_eq!(map.len(), 64);
+        assert_eq!(map.get("key42"), Some(&Value::Int(42)));
+        assert_eq!(map.get_index(0).unwrap().0, "key0");
+        assert_eq!(map.get("missing"), None);
+    }
 
     #[test]
     fn test_value_new() {
@@ -357,7 +917,7 @@ mod test {
 
     #[test]
     fn test_value_get_table() {
-        let mut map = Map::new();
+        let mut map = Map::default();
         map.insert("key".to_string(), Value::String("value".to_string()));
         let value = Value::new(Value::Table(map));
         assert_eq!(value.get("key"), Some(&Value::String("value".to_string())));
@@ -373,15 +933,41 @@ mod test {
     fn test_value_is_table() {
         let value = Value::new(Value::None);
         assert!(!value.is_table());
-        let mut map = Map::new();
+        let mut map = Map::default();
         map.insert("key".to_string(), Value::String("value".to_string()));
         let value = Value::new(Value::Table(map));
         assert!(value.is_table());
     }
 
+    #[test]
+    fn test_value_is_predicates() {
+        let values = [
+            Value::None,
+            Value::Array(vec![]),
+            Value::Table(Map::default()),
+            Value::String("test".to_string()),
+            Value::Float(1.0),
+            Value::Int(1),
+            Value::Bool(true),
+        ];
+        for value in values.iter() {
+            assert_eq!(value.is_none(), matches!(value, Value::None));
+            assert_eq!(value.is_array(), matches!(value, Value::Array(_)));
+            assert_eq!(value.is_table(), matches!(value, Value::Table(_)));
+            assert_eq!(value.is_string(), matches!(value, Value::String(_)));
+            assert_eq!(value.is_float(), matches!(value, Value::Float(_)));
+            assert_eq!(value.is_int(), matches!(value, Value::Int(_)));
+            assert_eq!(value.is_bool(), matches!(value, Value::Bool(_)));
+        }
+        assert!(Value::Int(1).is_number());
+        assert!(Value::Float(1.0).is_number());
+        assert!(!Value::String("1".to_string()).is_number());
+        assert!(!Value::None.is_number());
+    }
+
     #[test]
     fn test_value_get_mut() {
-        let mut map = Map::new();
+        let mut map = Map::default();
         map.insert("key".to_string(), Value::String("value".to_string()));
         let mut value = Value::new(Value::Table(map));
         assert_eq!(
@@ -396,6 +982,183 @@ mod test {
         assert_eq!(value.get_mut("key"), None);
     }
 
+    #[test]
+    fn test_value_kind() {
+        assert_eq!(Value::None.kind(), ValueKind::None);
+        assert_eq!(Value::Array(vec![]).kind(), ValueKind::Array);
+        assert_eq!(Value::Table(Map::default()).kind(), ValueKind::Table);
+        assert_eq!(Value::String("test".to_string()).kind(), ValueKind::String);
+        assert_eq!(Value::Float(1.0).kind(), ValueKind::Float);
+        assert_eq!(Value::Int(1).kind(), ValueKind::Int);
+        assert_eq!(Value::Bool(true).kind(), ValueKind::Bool);
+        assert_eq!(ValueKind::Table.to_string(), "Table");
+    }
+
+    #[test]
+    fn test_value_as_integer_strict() {
+        assert_eq!(Value::Int(3).as_integer_strict(), Ok(3));
+        assert_eq!(Value::Float(3.0).as_integer_strict(), Ok(3));
+        assert_eq!(
+            Value::Float(3.9).as_integer_strict(),
+            Err(CannotConvert::new("Float", "Int"))
+        );
+        assert_eq!(
+            Value::Float(f64::NAN).as_integer_strict(),
+            Err(CannotConvert::new("Float", "Int"))
+        );
+        assert_eq!(
+            Value::Float(1e30).as_integer_strict(),
+            Err(CannotConvert::new("Float", "Int"))
+        );
+        assert_eq!(
+            Value::String("3".to_string()).as_integer_strict(),
+            Err(CannotConvert::new("String", "Int"))
+        );
+    }
+
+    #[test]
+    fn test_value_infer_types() {
+        let mut value = Value::Table(Map::from_iter([
+            ("int".to_string(), Value::from("42")),
+            ("negative".to_string(), Value::from(" -3 ")),
+            ("float".to_string(), Value::from("1.5")),
+            ("bool".to_string(), Value::from("true")),
+            (
+                "list".to_string(),
+                Value::Array(vec![Value::from("False"), Value::from("0.5")]),
+            ),
+            ("mixed".to_string(), Value::from("42abc")),
+            ("zero_padded".to_string(), Value::from("007")),
+            ("nan".to_string(), Value::from("nan")),
+            ("yes".to_string(), Value::from("yes")),
+            ("empty".to_string(), Value::from("")),
+        ]));
+        value.infer_types();
+        assert_eq!(value.get("int"), Some(&Value::Int(42)));
+        assert_eq!(value.get("negative"), Some(&Value::Int(-3)));
+        assert_eq!(value.get("float"), Some(&Value::Float(1.5)));
+        assert_eq!(value.get("bool"), Some(&Value::Bool(true)));
+        assert_eq!(
+            value.get("list"),
+            Some(&Value::Array(vec![Value::Bool(false), Value::Float(0.5)]))
+        );
+        assert_eq!(value.get("mixed"), Some(&Value::from("42abc")));
+        assert_eq!(value.get("zero_padded"), Some(&Value::from("007")));
+        assert_eq!(value.get("nan"), Some(&Value::from("nan")));
+        assert_eq!(value.get("yes"), Some(&Value::from("yes")));
+        assert_eq!(value.get("empty"), Some(&Value::from("")));
+    }
+
+    #[test]
+    fn test_value_as_num_or_str() {
+        assert_eq!(Value::Int(30).as_num_or_str(), Some(NumOrStr::Int(30)));
+        assert_eq!(
+            Value::Float(1.5).as_num_or_str(),
+            Some(NumOrStr::Float(1.5))
+        );
+        assert_eq!(
+            Value::String("30s".to_string()).as_num_or_str(),
+            Some(NumOrStr::Str("30s"))
+        );
+        assert_eq!(
+            Value::from(u64::MAX).as_num_or_str(),
+            Some(NumOrStr::UInt(u64::MAX))
+        );
+        #[cfg(feature = "i128")]
+        assert_eq!(
+            Value::from(i128::MAX).as_num_or_str(),
+            Some(NumOrStr::BigInt(i128::MAX))
+        );
+        assert_eq!(Value::Bool(true).as_num_or_str(), None);
+    }
+
+    #[test]
+    fn test_value_into_table_array() {
+        let table = Map::from_iter([("key".to_string(), Value::Int(1))]);
+        assert_eq!(Value::Table(table.clone()).into_table(), Some(table));
+        assert_eq!(Value::Array(vec![]).into_table(), None);
+        assert_eq!(Value::None.into_table(), None);
+
+        let array = vec![Value::Int(1), Value::from("a")];
+        assert_eq!(Value::Array(array.clone()).into_array(), Some(array));
+        assert_eq!(Value::Table(Map::default()).into_array(), None);
+        assert_eq!(Value::from("a").into_array(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_value_check_representable() {
+        use crate::file::FileFormat;
+
+        let table = |entries: Vec<(&str, Value)>| {
+            Value::Table(Map::from_iter(
+                entries.into_iter().map(|(k, v)| (k.to_string(), v)),
+            ))
+        };
+        let config = table(vec![(
+            "db",
+            table(vec![("host", Value::from("a")), ("password", Value::None)]),
+        )]);
+        assert_eq!(
+            config.check_representable(FileFormat::Toml),
+            Err(ConfigError::Format {
+                format: FileFormat::Toml,
+                message: "db.password: TOML does not support null values".to_string(),
+            })
+        );
+        assert_eq!(config.check_representable(FileFormat::Json), Ok(()));
+        assert_eq!(config.check_representable(FileFormat::Ini), Ok(()));
+
+        let config = table(vec![
+            ("name", Value::from("app")),
+            ("ports", Value::Array(vec![Value::Int(1), Value::Int(2)])),
+            (
+                "server",
+                table(vec![("tls", table(vec![("cert", Value::from("a.pem"))]))]),
+            ),
+        ]);
+        assert_eq!(
+            config.check_representable(FileFormat::Ini),
+            Err(ConfigError::Format {
+                format: FileFormat::Ini,
+                message: "server.tls: INI does not support nesting beyond one level of sections"
+                    .to_string(),
+            })
+        );
+        assert_eq!(config.check_representable(FileFormat::Toml), Ok(()));
+
+        let nested = table(vec![(
+            "list",
+            Value::Array(vec![Value::Array(vec![Value::Int(1)])]),
+        )]);
+        assert_eq!(
+            nested.check_representable(FileFormat::Ini),
+            Err(ConfigError::Format {
+                format: FileFormat::Ini,
+                message: "list[0]: INI does not support nested arrays".to_string(),
+            })
+        );
+
+        let floats = table(vec![("ratio", Value::Float(f64::NAN))]);
+        assert_eq!(
+            floats.check_representable(FileFormat::Json),
+            Err(ConfigError::Format {
+                format: FileFormat::Json,
+                message: "ratio: JSON does not support NaN or infinite floats".to_string(),
+            })
+        );
+        assert_eq!(floats.check_representable(FileFormat::Yaml), Ok(()));
+
+        assert_eq!(
+            Value::Int(1).check_representable(FileFormat::Toml),
+            Err(ConfigError::Format {
+                format: FileFormat::Toml,
+                message: "the root must be a table".to_string(),
+            })
+        );
+        assert_eq!(Value::Int(1).check_representable(FileFormat::Ron), Ok(()));
+    }
+
     #[test]
     fn test_value_display() {
         let value = Value::String("test".to_string());
@@ -408,7 +1171,7 @@ mod test {
         assert_eq!(value.to_string(), "true");
         let value = Value::Array(vec![Value::String("test".to_string())]);
         assert_eq!(value.to_string(), "[\"test\"]");
-        let mut map = Map::new();
+        let mut map = Map::default();
         map.insert("key".to_string(), Value::String("value".to_string()));
         let value = Value::Table(map);
         assert_eq!(value.to_string(), "{(key: \"value\")}");
@@ -416,12 +1179,33 @@ mod test {
         assert_eq!(value.to_string(), "null");
     }
 
+    #[test]
+    fn test_value_display_pretty() {
+        let mut map = Map::default();
+        map.insert("key".to_string(), Value::String("value".to_string()));
+        map.insert(
+            "array".to_string(),
+            Value::Array(vec![Value::Int(1), Value::Table(Map::default())]),
+        );
+        map.insert("empty".to_string(), Value::Array(vec![]));
+        let value = Value::Table(map);
+        assert_eq!(
+            format!("{}", value),
+            "{(key: \"value\"), (array: [1, {}]), (empty: [])}"
+        );
+        assert_eq!(
+            format!("{:#}", value),
+            "{\n  key: \"value\",\n  array: [\n    1,\n    {}\n  ],\n  empty: []\n}"
+        );
+        assert_eq!(format!("{:#}", Value::Int(1)), "1");
+    }
+
     mod value_from {
         use super::*;
 
         fn test_value_from<T: Into<Value>>(value: T, expected: Value)
         where
-            Value: std::convert::From<T>,
+            Value: core::convert::From<T>,
         {
             let result = Value::from(value);
             assert_eq!(result, expected);
@@ -436,7 +1220,7 @@ mod test {
 
         #[test]
         fn test_value_from_map() {
-            let value = Map::new();
+            let value = Map::default();
             let expected = Value::Table(value.clone());
             test_value_from(value, expected);
         }
@@ -516,6 +1300,36 @@ mod test {
             let value: u64 = 1;
             let expected = Value::Int(1);
             test_value_from(value, expected);
+            test_value_from(i64::MAX as u64, Value::Int(i64::MAX));
+            test_value_from(i64::MAX as u64 + 1, Value::UInt(i64::MAX as u64 + 1));
+            test_value_from(u64::MAX, Value::UInt(u64::MAX));
+            test_value_from(u64::MAX as i128, Value::UInt(u64::MAX));
+            #[cfg(not(feature = "i128"))]
+            {
+                test_value_from(i128::MIN, Value::Int(i64::MIN));
+                test_value_from(u128::MAX, Value::UInt(u64::MAX));
+            }
+            #[cfg(feature = "i128")]
+            {
+                test_value_from(i128::MIN, Value::BigInt(i128::MIN));
+                test_value_from(u128::MAX, Value::BigInt(i128::MAX));
+                test_value_from(i64::MAX as i128, Value::Int(i64::MAX));
+            }
+        }
+
+        #[test]
+        #[cfg(feature = "i128")]
+        fn test_value_big_int() {
+            let value = Value::from(i128::MAX);
+            assert!(value.is_int());
+            assert_eq!(value.kind(), ValueKind::Int);
+            assert_eq!(value.to_string(), i128::MAX.to_string());
+            let result: Result<i128, CannotConvert> = value.clone().try_into();
+            assert_eq!(result, Ok(i128::MAX));
+            let result: Result<i64, CannotConvert> = value.try_into();
+            assert_eq!(result, Err(CannotConvert::new("BigInt", "Int")));
+            let result: Result<i128, CannotConvert> = Value::Int(1).try_into();
+            assert_eq!(result, Ok(1));
         }
 
         #[test]
@@ -599,7 +1413,7 @@ mod test {
             assert_eq!(result, Ok("true".to_string()));
             assert_ne!(result, Err(CannotConvert::new("Bool", "String")));
 
-            let value = Value::Table(Map::new());
+            let value = Value::Table(Map::default());
             let result: Result<String, CannotConvert> = value.try_into();
             assert_eq!(result, Err(CannotConvert::new("Table", "String")));
             assert_ne!(result, Ok("".to_string()));
@@ -637,7 +1451,7 @@ mod test {
             let result: Result<f64, CannotConvert> = value.try_into();
             assert_eq!(result, Ok(0.0));
 
-            let value = Value::Table(Map::new());
+            let value = Value::Table(Map::default());
             let result: Result<f64, CannotConvert> = value.try_into();
             assert_eq!(result, Err(CannotConvert::new("Table", "Float")));
         }
@@ -674,11 +1488,35 @@ mod test {
             let result: Result<i64, CannotConvert> = value.try_into();
             assert_eq!(result, Ok(0));
 
-            let value = Value::Table(Map::new());
+            let value = Value::Table(Map::default());
             let result: Result<i64, CannotConvert> = value.try_into();
             assert_eq!(result, Err(CannotConvert::new("Table", "Int")));
         }
 
+        #[test]
+        fn test_value_try_into_u64() {
+            let result: Result<u64, CannotConvert> = Value::Int(1).try_into();
+            assert_eq!(result, Ok(1));
+            let result: Result<u64, CannotConvert> = Value::Int(-1).try_into();
+            assert_eq!(result, Err(CannotConvert::new("Int", "UInt")));
+            let result: Result<u64, CannotConvert> = Value::Float(-1.0).try_into();
+            assert_eq!(result, Err(CannotConvert::new("Float", "UInt")));
+            let result: Result<u64, CannotConvert> = Value::from("18446744073709551615").try_into();
+            assert_eq!(result, Ok(u64::MAX));
+            let result: Result<u64, CannotConvert> = Value::Array(vec![]).try_into();
+            assert_eq!(result, Err(CannotConvert::new("Array", "UInt")));
+
+            let result: Result<u64, CannotConvert> = Value::from(u64::MAX).try_into();
+            assert_eq!(result, Ok(u64::MAX));
+            let result: Result<i64, CannotConvert> = Value::from(u64::MAX).try_into();
+            assert_eq!(result, Err(CannotConvert::new("UInt", "Int")));
+            #[cfg(feature = "i128")]
+            {
+                let result: Result<u64, CannotConvert> = Value::from(u128::MAX).try_into();
+                assert_eq!(result, Err(CannotConvert::new("BigInt", "UInt")));
+            }
+        }
+
         #[test]
         fn test_value_try_into_bool() {
             let value = Value::String("true".to_string());
@@ -690,6 +1528,14 @@ mod test {
             let value = Value::String("false".to_string());
             let result: Result<bool, CannotConvert> = value.try_into();
             assert_eq!(result, Ok(false));
+            for truthy in ["yes", "Y", "on", "ON", "1", "t"] {
+                let result: Result<bool, CannotConvert> = Value::from(truthy).try_into();
+                assert_eq!(result, Ok(true), "{}", truthy);
+            }
+            for falsy in ["no", "off", "0", "f", ""] {
+                let result: Result<bool, CannotConvert> = Value::from(falsy).try_into();
+                assert_eq!(result, Ok(false), "{}", falsy);
+            }
 
             let value = Value::None;
             let result: Result<bool, CannotConvert> = value.try_into();
@@ -720,7 +1566,7 @@ mod test {
             let result: Result<bool, CannotConvert> = value.try_into();
             assert_eq!(result, Ok(false));
 
-            let value = Value::Table(Map::new());
+            let value = Value::Table(Map::default());
             let result: Result<bool, CannotConvert> = value.try_into();
             assert_eq!(result, Err(CannotConvert::new("Table", "Bool")));
         }
@@ -739,7 +1585,7 @@ mod test {
             let result: Result<Vec<Value>, CannotConvert> = value.try_into();
             assert_eq!(result, Err(CannotConvert::new("String", "Array")));
 
-            let value = Value::Table(Map::new());
+            let value = Value::Table(Map::default());
             let result: Result<Vec<Value>, CannotConvert> = value.try_into();
             assert_eq!(result, Err(CannotConvert::new("Table", "Array")));
 
@@ -773,13 +1619,13 @@ mod test {
 
         #[test]
         fn test_value_try_into_map() {
-            let value = Value::Table(Map::new());
+            let value = Value::Table(Map::default());
             let result: Result<Map<String, Value>, CannotConvert> = value.try_into();
-            assert_eq!(result, Ok(Map::new()));
+            assert_eq!(result, Ok(Map::default()));
 
             let value = Value::None;
             let result: Result<Map<String, Value>, CannotConvert> = value.try_into();
-            assert_eq!(result, Ok(Map::new()));
+            assert_eq!(result, Ok(Map::default()));
 
             let value = Value::String("test".to_string());
             let result: Result<Map<String, Value>, CannotConvert> = value.try_into();
@@ -801,5 +1647,23 @@ mod test {
             let result: Result<Map<String, Value>, CannotConvert> = value.try_into();
             assert_eq!(result, Err(CannotConvert::new("Int", "Table")));
         }
+
+        #[test]
+        fn test_value_try_into_option() {
+            let result: Result<Option<i64>, CannotConvert> = Value::None.try_into();
+            assert_eq!(result, Ok(None));
+
+            let result: Result<Option<i64>, CannotConvert> = Value::Int(5).try_into();
+            assert_eq!(result, Ok(Some(5)));
+
+            let result: Result<Option<i64>, CannotConvert> = Value::Array(vec![]).try_into();
+            assert_eq!(result, Err(CannotConvert::new("Array", "Int")));
+
+            let result: Result<Option<String>, CannotConvert> = Value::None.try_into();
+            assert_eq!(result, Ok(None));
+
+            let result: Result<Option<bool>, CannotConvert> = Value::from("yes").try_into();
+            assert_eq!(result, Ok(Some(true)));
+        }
     }
 }
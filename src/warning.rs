@@ -0,0 +1,79 @@
+//! Non-fatal warnings collected while building a config
+
+/// A warning that does not prevent a config from being built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// A deprecated key is present, with an optional replacement key.
+    Deprecated {
+        key: String,
+        replacement: Option<String>,
+    },
+    /// A loaded change was ignored because its key is not present in defaults, holding the
+    /// dotted path of the key.
+    IgnoredChange(String),
+    /// Two files set the same key to different values, the second one wins.
+    Conflict {
+        key: String,
+        first: String,
+        second: String,
+    },
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::Deprecated {
+                key,
+                replacement: Some(replacement),
+            } => write!(f, "Key {} is deprecated, use {} instead", key, replacement),
+            Warning::Deprecated {
+                key,
+                replacement: None,
+            } => write!(f, "Key {} is deprecated", key),
+            Warning::IgnoredChange(key) => {
+                write!(f, "Change to key {} ignored, key is not in defaults", key)
+            }
+            Warning::Conflict { key, first, second } => write!(
+                f,
+                "Key {} is set to different values in {} and {}",
+                key, first, second
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_warning_display() {
+        let warning = Warning::Deprecated {
+            key: "old".to_string(),
+            replacement: Some("new".to_string()),
+        };
+        assert_eq!(
+            warning.to_string(),
+            "Key old is deprecated, use new instead"
+        );
+        let warning = Warning::Deprecated {
+            key: "old".to_string(),
+            replacement: None,
+        };
+        assert_eq!(warning.to_string(), "Key old is deprecated");
+        let warning = Warning::IgnoredChange("key".to_string());
+        assert_eq!(
+            warning.to_string(),
+            "Change to key key ignored, key is not in defaults"
+        );
+        let warning = Warning::Conflict {
+            key: "key".to_string(),
+            first: "a.json".to_string(),
+            second: "b.json".to_string(),
+        };
+        assert_eq!(
+            warning.to_string(),
+            "Key key is set to different values in a.json and b.json"
+        );
+    }
+}